@@ -54,7 +54,7 @@ mod wrap_it {
         for _ in 0..10 {
             span_context!(Span::current());
             channel.tx.send(Foo).await?;
-            if let Some(_) = channel.rx.recv().await {}
+            channel.rx.recv().await;
         }
         tokio::time::delay_for(std::time::Duration::from_millis(500)).await;
         Ok(())
@@ -64,7 +64,7 @@ mod wrap_it {
     async fn b(mut channel: MyChannel, mut to_c: MyChannel) -> Result<(), Box<dyn Error>> {
         for _ in 0..10 {
             span_context!(Span::current());
-            if let Some(_) = channel.rx.recv().await {}
+            channel.rx.recv().await;
             channel.tx.send(Foo).await?;
             to_c.tx.send(Foo).await?;
         }
@@ -76,7 +76,7 @@ mod wrap_it {
     async fn c(mut from_b_to_a: MyChannel) -> Result<(), Box<dyn Error>> {
         for _ in 0..10 {
             span_context!(Span::current());
-            if let Some(_) = from_b_to_a.rx.recv().await {}
+            from_b_to_a.rx.recv().await;
             from_b_to_a.tx.send(Foo).await?;
         }
         tokio::time::delay_for(std::time::Duration::from_millis(500)).await;
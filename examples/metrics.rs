@@ -2,7 +2,14 @@ use observability::metrics;
 use tracing::*;
 use std::error::Error;
 
-metrics!(MyMetric, CounterA, CounterB);
+metrics!(
+    MyMetric,
+    CounterA,
+    CounterB,
+    counter Requests{peer},
+    gauge Connections,
+    histogram LatencyMs
+);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
@@ -19,6 +26,15 @@ async fn main() -> Result<(), Box<dyn Error>> {
     MyMetric::count_filter(MyMetric::CounterA, 10, "my_filter");
     MyMetric::count_filter(MyMetric::CounterA, 10, "my_other_filter");
 
+    MyMetric::with_labels(MyMetric::Requests, &["peer_a"]).count(1);
+    MyMetric::with_labels(MyMetric::Requests, &["peer_b"]).count(2);
+
+    MyMetric::inc(MyMetric::Connections);
+    MyMetric::dec(MyMetric::Connections);
+    for latency in &[3, 12, 40] {
+        MyMetric::record(MyMetric::LatencyMs, *latency);
+    }
+
     MyMetric::print();
     let mut td = std::env::temp_dir();
     td.push("metrics_csv");
@@ -26,5 +42,10 @@ async fn main() -> Result<(), Box<dyn Error>> {
     td.push("metrics.csv");
     MyMetric::save_csv(&td);
 
+    // Every declared set.
+    metrics::print_all();
+    td.set_file_name("all_metrics.csv");
+    metrics::save_all_csv(&td);
+
     Ok(())
 }
\ No newline at end of file
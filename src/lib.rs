@@ -91,11 +91,24 @@ mod fmt;
 pub mod metrics;
 mod open;
 
-#[cfg(all(feature = "opentelemetry-on", feature = "channels"))]
+#[cfg(any(
+    feature = "channels",
+    feature = "std-channels",
+    feature = "crossbeam-channels"
+))]
 pub use open::channel;
-#[cfg(feature = "opentelemetry-on")]
+#[cfg(feature = "codec")]
+pub use open::codec;
+#[cfg(feature = "http")]
+pub use open::http;
 pub use open::should_run;
-pub use open::{Config, Context, MsgWrap, OpenSpanExt};
+#[cfg(feature = "tasks")]
+pub use open::spawn::task;
+pub use open::spawn::thread;
+pub use open::{
+    Config, Context, FutureExt, HeaderExtractor, HeaderInjector, InContext, MsgWrap, OpenSpanExt,
+    Propagator,
+};
 
 pub use tracing;
 
@@ -303,10 +316,19 @@ pub fn init_fmt(output: Output) -> Result<(), errors::TracingError> {
             {
                 use open::OPEN_ON;
                 use opentelemetry::api::Provider;
+                open::init()?;
                 OPEN_ON.store(true, std::sync::atomic::Ordering::SeqCst);
                 use tracing_subscriber::prelude::*;
-                open::init();
-                let tracer = opentelemetry::sdk::Provider::default().get_tracer("component_name");
+                let config = opentelemetry::sdk::Config {
+                    resource: std::sync::Arc::new(opentelemetry::sdk::Resource::new(
+                        open::resource(),
+                    )),
+                    ..Default::default()
+                };
+                let tracer = opentelemetry::sdk::Provider::builder()
+                    .with_config(config)
+                    .build()
+                    .get_tracer("component_name");
                 let telemetry = tracing_opentelemetry::layer().with_tracer(tracer);
                 finish(
                     subscriber
@@ -325,6 +347,13 @@ pub fn init_fmt(output: Output) -> Result<(), errors::TracingError> {
     }
 }
 
+/// Same as [init_fmt] but uses this config for open telemetry
+/// instead of reading it from `OPEN_TEL`.
+pub fn init_with_config(output: Output, config: Config) -> Result<(), errors::TracingError> {
+    open::set_config(config)?;
+    init_fmt(output)
+}
+
 fn finish<S>(subscriber: S) -> Result<(), errors::TracingError>
 where
     S: Subscriber + Send + Sync + for<'span> LookupSpan<'span>,
@@ -351,5 +380,35 @@ pub mod errors {
         TracingFlame,
         #[error(transparent)]
         BadDirective(#[from] tracing_subscriber::filter::ParseError),
+        #[error(transparent)]
+        BadConfig(#[from] ConfigError),
+        #[error("The open telemetry config has already been set")]
+        ConfigAlreadySet,
+    }
+
+    /// Error parsing the open telemetry [crate::Config]
+    #[allow(missing_docs)] // should be self-explanatory
+    #[derive(Error, Debug, Clone, PartialEq, Eq)]
+    pub enum ConfigError {
+        #[error("Expected `key: value` but found `{0}`")]
+        Format(String),
+        #[error("Unknown config key `{0}`")]
+        UnknownKey(String),
+        #[error("Bad value `{value}` for config key `{key}`")]
+        BadValue { key: String, value: String },
+    }
+
+    /// Error decoding a context that was sent over the network
+    #[allow(missing_docs)] // should be self-explanatory
+    #[derive(Error, Debug, Clone, PartialEq, Eq)]
+    pub enum WireError {
+        #[error("Failed to deserialize wire context: {0}")]
+        Deserialize(String),
+        #[error("Unsupported wire context version: {0}")]
+        UnsupportedVersion(u8),
+        #[error("Trace id should be 16 bytes but was {0}")]
+        TraceIdLength(usize),
+        #[error("Span id should be 8 bytes but was {0}")]
+        SpanIdLength(usize),
     }
 }
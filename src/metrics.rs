@@ -5,13 +5,23 @@
 //! is on the stack.
 //! This means you need to keep the metric sets small (<100 metrics per set).
 //! If you need more then make a new set.
-use std::sync::atomic::AtomicBool;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
 #[allow(missing_docs)]
 #[doc(hidden)]
 static METRICS_ON: AtomicBool = AtomicBool::new(false);
 
+/// Default maximum number of label combinations per metric.
+pub const DEFAULT_LABEL_CAP: usize = 100;
+/// The label value used for every label when a
+/// metric has reached its label cap.
+pub const OVERFLOW_LABEL: &str = "overflow";
+static LABEL_CAP: AtomicUsize = AtomicUsize::new(DEFAULT_LABEL_CAP);
+
 /// Enable all metrics for your program
 pub fn init() {
+    #[cfg(feature = "otlp-metrics")]
+    otlp::start();
     METRICS_ON.store(true, std::sync::atomic::Ordering::SeqCst);
 }
 
@@ -21,25 +31,243 @@ pub fn is_enabled() -> bool {
     METRICS_ON.load(std::sync::atomic::Ordering::Relaxed)
 }
 
+/// Set the maximum number of label combinations tracked
+/// for each metric. Combinations past the cap are all
+/// counted in one series with every label set to [`OVERFLOW_LABEL`].
+pub fn set_label_cap(cap: usize) {
+    LABEL_CAP.store(cap, Ordering::Relaxed);
+}
+
+/// The kind of a metric.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Kind {
+    /// Only goes up.
+    Counter,
+    /// Can be set and go up or down.
+    Gauge,
+    /// Distribution of recorded values.
+    Histogram,
+}
+
+/// The value of a metric.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Value {
+    /// Total count.
+    Counter(u64),
+    /// Current value.
+    Gauge(i64),
+    /// Distribution so far.
+    Histogram(Histogram),
+}
+
+/// Number of histogram buckets.
+/// Bucket `i` holds values up to `2^i - 1`.
+pub const BUCKETS: usize = 65;
+
+/// The recorded distribution of a histogram.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Histogram {
+    /// Number of values recorded.
+    pub count: u64,
+    /// Sum of all values recorded.
+    pub sum: u64,
+    /// Number of values in each bucket.
+    pub buckets: Vec<u64>,
+}
+
+impl Histogram {
+    /// The largest value in a bucket.
+    pub fn bucket_bound(bucket: usize) -> u64 {
+        match bucket {
+            0 => 0,
+            64..=usize::MAX => u64::MAX,
+            b => (1u64 << b) - 1,
+        }
+    }
+
+    /// The bucket a value goes in.
+    pub fn bucket(value: u64) -> usize {
+        64 - value.leading_zeros() as usize
+    }
+
+    /// Mean of the recorded values.
+    pub fn mean(&self) -> f64 {
+        if self.count == 0 {
+            0.0
+        } else {
+            self.sum as f64 / self.count as f64
+        }
+    }
+
+    /// Approximate quantile (`0.0..=1.0`) of the recorded values.
+    /// This is interpolated within the bucket so it is only
+    /// accurate to the bucket's range.
+    pub fn quantile(&self, q: f64) -> u64 {
+        if self.count == 0 {
+            return 0;
+        }
+        let target = ((q.clamp(0.0, 1.0) * self.count as f64).ceil() as u64).max(1);
+        let mut seen = 0;
+        for (i, &n) in self.buckets.iter().enumerate() {
+            if n > 0 && seen + n >= target {
+                if i == 0 {
+                    return 0;
+                }
+                let low = Self::bucket_bound(i - 1) + 1;
+                let high = Self::bucket_bound(i);
+                let fraction = (target - seen) as f64 / n as f64;
+                return low + ((high - low) as f64 * fraction) as u64;
+            }
+            seen += n;
+        }
+        Self::bucket_bound(BUCKETS - 1)
+    }
+}
+
+/// The label keys and values of one series of a metric.
+/// Empty for the series without labels.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Labels(pub Vec<(&'static str, String)>);
+
+impl Labels {
+    /// Is this the series without labels?
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl std::fmt::Display for Labels {
+    /// Formatted like `{peer="a",zome="b"}`.
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        write!(f, "{{")?;
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, ",")?;
+            }
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            write!(f, "{}=\"{}\"", key, value)?;
+        }
+        write!(f, "}}")
+    }
+}
+
+/// One label combination of a metric.
+/// Get it with `MySet::with_labels` and keep it
+/// around to skip looking up the labels again.
+/// Updates don't emit tracing events.
+#[derive(Clone)]
+pub struct Series {
+    slot: Arc<__inner::Slot>,
+    kind: Kind,
+}
+
+impl Series {
+    /// Add to this counter.
+    pub fn count(&self, n: u64) {
+        __inner::slot_count(&self.slot, n);
+    }
+    /// Set this gauge.
+    pub fn set(&self, value: i64) {
+        debug_assert_eq!(self.kind, Kind::Gauge, "Only gauges can be set");
+        __inner::slot_set(&self.slot, value);
+    }
+    /// Add one to this gauge.
+    pub fn inc(&self) {
+        debug_assert_eq!(self.kind, Kind::Gauge, "Only gauges can go up");
+        __inner::slot_add(&self.slot, 1);
+    }
+    /// Subtract one from this gauge.
+    pub fn dec(&self) {
+        debug_assert_eq!(self.kind, Kind::Gauge, "Only gauges can go down");
+        __inner::slot_add(&self.slot, -1);
+    }
+    /// Record a value in this histogram.
+    pub fn record(&self, value: u64) {
+        debug_assert_eq!(self.kind, Kind::Histogram, "Only histograms can record");
+        __inner::slot_record(&self.slot, value);
+    }
+    /// Get the current value of this series.
+    pub fn value(&self) -> Value {
+        __inner::load(&self.slot, self.kind)
+    }
+}
+
 /// Create a metrics set.
 /// Takes the name of the metric set followed by
 /// a list of metric names.
+/// Each metric can be prefixed with its kind:
+/// `counter` (the default), `gauge` or `histogram`
+/// and followed by the keys of its labels.
+/// Doc comments on a metric are used as its description.
+/// The set is `pub` unless it is given a visibility
+/// and any number of sets can be declared in one module.
+/// Every declared set is registered before `main` so
+/// the `*_all` functions include it even if it's never used.
+/// Set names should be unique in the program. Sets that
+/// share a name are shown as `module::path::Name` instead.
+/// ```
+/// # use observability::metrics;
+/// metrics!(Net, counter Sent{peer}, gauge Open, histogram Latency);
+/// metrics!(
+///     /// Calls to other nodes.
+///     pub(crate) Calls,
+///     /// Calls that timed out.
+///     counter TimedOut{peer},
+/// );
+/// ```
 #[macro_export]
 macro_rules! metrics {
-    ($name:ident, $($metric:ident),+) => {
+    (@parse $head:tt [$($out:tt)*] $(#[doc = $doc:literal])* counter $metric:ident $({$($label:ident),* $(,)?})? $(, $($rest:tt)*)?) => {
+        $crate::metrics!(@parse $head [$($out)* (Counter $metric [$($($label)*)?] [$($doc)*])] $($($rest)*)?);
+    };
+    (@parse $head:tt [$($out:tt)*] $(#[doc = $doc:literal])* gauge $metric:ident $({$($label:ident),* $(,)?})? $(, $($rest:tt)*)?) => {
+        $crate::metrics!(@parse $head [$($out)* (Gauge $metric [$($($label)*)?] [$($doc)*])] $($($rest)*)?);
+    };
+    (@parse $head:tt [$($out:tt)*] $(#[doc = $doc:literal])* histogram $metric:ident $({$($label:ident),* $(,)?})? $(, $($rest:tt)*)?) => {
+        $crate::metrics!(@parse $head [$($out)* (Histogram $metric [$($($label)*)?] [$($doc)*])] $($($rest)*)?);
+    };
+    (@parse $head:tt [$($out:tt)*] $(#[doc = $doc:literal])* $metric:ident $({$($label:ident),* $(,)?})? $(, $($rest:tt)*)?) => {
+        $crate::metrics!(@parse $head [$($out)* (Counter $metric [$($($label)*)?] [$($doc)*])] $($($rest)*)?);
+    };
+    (@parse [$(#[$meta:meta])* $vis:vis $name:ident] [$(($kind:ident $metric:ident [$($label:ident)*] [$($doc:literal)*]))+]) => {
+        $(#[$meta])*
         #[allow(missing_docs)]
         #[derive(Debug, Copy, Clone)]
-        pub enum $name {
-            $($metric),+
-        }
-
-        mod metrics_inner {
-            pub(crate) const NUM: usize = 0usize $(+ $crate::__replace_expr!($metric 1usize))+;
-            pub(crate) static METRICS: [std::sync::atomic::AtomicU64; NUM] = [$($crate::__replace_expr!($metric std::sync::atomic::AtomicU64::new(0))),+];
-            pub(crate) const NAMES: [&'static str; NUM] = [$(stringify!($metric)),+];
+        $vis enum $name {
+            $($(#[doc = $doc])* $metric),+
         }
 
         impl $name {
+            /// The statics of this set live in here so
+            /// many sets can share a module.
+            fn __set() -> &'static $crate::metrics::__inner::Set {
+                const NUM: usize = 0usize $(+ $crate::__replace_expr!($metric 1usize))+;
+                static METRICS: [$crate::metrics::__inner::Slot; NUM] = [$($crate::__replace_expr!($metric $crate::metrics::__inner::Slot::new())),+];
+                const NAMES: [&'static str; NUM] = [$(stringify!($metric)),+];
+                const KINDS: [$crate::metrics::Kind; NUM] = [$($crate::metrics::Kind::$kind),+];
+                const LABELS: [&'static [&'static str]; NUM] = [$(&[$(stringify!($label)),*]),+];
+                const DOCS: [&'static [&'static str]; NUM] = [$(&[$($doc),*]),+];
+                static SET: $crate::metrics::__inner::Set = $crate::metrics::__inner::Set {
+                    name: stringify!($name),
+                    path: module_path!(),
+                    shares_name: std::sync::atomic::AtomicBool::new(false),
+                    metrics: &METRICS,
+                    names: &NAMES,
+                    kinds: &KINDS,
+                    labels: &LABELS,
+                    docs: &DOCS,
+                    reset_at: std::sync::atomic::AtomicU64::new(0),
+                };
+                &SET
+            }
             /// Add to this counter and emit tracing event
             pub fn count<N, E>(metric: Self, n: N)
             where
@@ -47,7 +275,7 @@ macro_rules! metrics {
                 std::num::TryFromIntError: From<E>,
                 N: std::convert::TryInto<u64, Error = E>,
             {
-                $crate::metrics::__inner::count(&metrics_inner::METRICS[..], &metrics_inner::NAMES[..], metric as usize, n, "none")
+                Self::__set().count(metric as usize, n, "none")
             }
             /// Add to this counter and emit tracing event
             /// with a field that can be used as a filter.
@@ -59,7 +287,7 @@ macro_rules! metrics {
                 std::num::TryFromIntError: From<E>,
                 N: std::convert::TryInto<u64, Error = E>,
             {
-                $crate::metrics::__inner::count(&metrics_inner::METRICS[..], &metrics_inner::NAMES[..], metric as usize, n, filter)
+                Self::__set().count(metric as usize, n, filter)
             }
             /// Add to this counter without emit tracing event
             pub fn count_silent<N, E>(metric: Self, n: N) -> u64
@@ -68,27 +296,91 @@ macro_rules! metrics {
                 std::num::TryFromIntError: From<E>,
                 N: std::convert::TryInto<u64, Error = E>,
             {
-                $crate::metrics::__inner::count_silent(&metrics_inner::METRICS[..], metric as usize, n)
+                Self::__set().count_silent(metric as usize, n)
+            }
+            /// Set this gauge
+            pub fn set(metric: Self, value: i64) {
+                Self::__set().set(metric as usize, value)
+            }
+            /// Add one to this gauge
+            pub fn inc(metric: Self) {
+                Self::__set().add(metric as usize, 1)
+            }
+            /// Subtract one from this gauge
+            pub fn dec(metric: Self) {
+                Self::__set().add(metric as usize, -1)
+            }
+            /// Record a value in this histogram
+            pub fn record<N, E>(metric: Self, value: N)
+            where
+                E: std::fmt::Debug,
+                std::num::TryFromIntError: From<E>,
+                N: std::convert::TryInto<u64, Error = E>,
+            {
+                Self::__set().record(metric as usize, value)
+            }
+            /// Get the series of this metric for these label values.
+            /// The values are in the same order as the label keys.
+            pub fn with_labels(metric: Self, values: &[&str]) -> $crate::metrics::Series {
+                Self::__set().with_labels(metric as usize, values)
             }
-            /// Get the current value of this metric
+            /// Get the current value of this metric without labels.
+            /// Gauges below zero are zero and histograms are
+            /// their count, use `value` for these instead.
             pub fn get(metric: Self) -> u64 {
-                $crate::metrics::__inner::get(&metrics_inner::METRICS[..], metric as usize)
+                Self::__set().get(metric as usize)
+            }
+            /// Get the current value of this metric without labels
+            /// for any kind of metric.
+            pub fn value(metric: Self) -> $crate::metrics::Value {
+                Self::__set().value(metric as usize)
+            }
+            /// Get an iterator over all metrics and their labels
+            pub fn iter() -> impl Iterator<Item = (Self, $crate::metrics::Labels, u64)> {
+                Self::__set().iter().map(|(n, l, i)| (n.into(), l, i))
+            }
+            /// Get an iterator over the values of all metrics and their labels
+            pub fn values() -> impl Iterator<Item = (Self, $crate::metrics::Labels, $crate::metrics::Value)> {
+                Self::__set().values().map(|(n, l, v)| (n.into(), l, v))
             }
-            /// Get an iterator over all metrics
-            pub fn iter() -> impl Iterator<Item = (Self, u64)> {
-                $crate::metrics::__inner::iter(&metrics_inner::METRICS[..], &metrics_inner::NAMES[..])
-                    .map(|(n, i)|(n.into(), i))
+            /// Get an owned copy of every metric and its labels
+            pub fn snapshot() -> $crate::metrics::Snapshot {
+                Self::__set().snapshot()
+            }
+            /// Set the counters and histograms back to zero
+            /// and get their values from before.
+            /// Gauges are not changed.
+            /// OTLP exports count from the time of the reset.
+            pub fn reset() -> $crate::metrics::Snapshot {
+                Self::__set().reset()
+            }
+            /// Get what changed since an earlier snapshot of this set
+            pub fn delta(since: &$crate::metrics::Snapshot) -> $crate::metrics::Snapshot {
+                Self::snapshot().delta(since)
             }
             /// Emit tracing events for every metric
             pub fn print() {
-                $crate::metrics::__inner::print(&metrics_inner::METRICS[..], &metrics_inner::NAMES[..])
+                Self::__set().print()
             }
             /// Save all metrics to csv
             pub fn save_csv(path: &std::path::Path) {
-                $crate::metrics::__inner::save_csv(&metrics_inner::METRICS[..], &metrics_inner::NAMES[..], path)
+                Self::__set().save_csv(path)
+            }
+            /// Render all metrics in the Prometheus text format
+            pub fn prometheus() -> String {
+                $crate::metrics::__inner::prometheus(&[Self::__set()])
             }
         }
 
+        // Every declared set is registered before main
+        // so it shows up even if it's never used.
+        const _: () = {
+            #[$crate::metrics::__inner::ctor]
+            fn register() {
+                $name::__set().register()
+            }
+        };
+
         impl From<&str> for $name {
             fn from(s: &str) -> Self {
                 use $name::*;
@@ -100,7 +392,28 @@ macro_rules! metrics {
         }
 
     };
+    // Sets without a visibility are public.
+    ($(#[$meta:meta])* $name:ident, $($metrics:tt)+) => {
+        $crate::metrics!(@parse [$(#[$meta])* pub $name] [] $($metrics)+);
+    };
+    ($(#[$meta:meta])* $vis:vis $name:ident, $($metrics:tt)+) => {
+        $crate::metrics!(@parse [$(#[$meta])* $vis $name] [] $($metrics)+);
+    };
 }
+metrics!(
+    /// Metrics recorded when a context crosses a boundary and
+    /// is set on a span.
+    Boundary,
+    /// Number of contexts received.
+    Crossings,
+    /// Total time between getting the context on the sender
+    /// side and setting it on the receiver side.
+    QueueLatencyMicros,
+    /// Total size of the messages that carried the
+    /// contexts when it is known.
+    MessageBytes,
+);
+
 #[macro_export]
 #[allow(missing_docs)]
 #[doc(hidden)]
@@ -110,83 +423,563 @@ macro_rules! __replace_expr {
     };
 }
 
+#[cfg(feature = "otlp-metrics")]
+pub use otlp::{export_otlp, start_otlp_export, OtlpExport};
+#[cfg(feature = "metrics-endpoint")]
+pub use prometheus::{serve_prometheus, PrometheusServer};
+pub use sampler::{sample_csv, CsvSampler};
+
+#[cfg(feature = "otlp-metrics")]
+mod otlp;
+mod periodic;
+mod prometheus;
+mod sampler;
+
+/// Render every registered metric set
+/// in the Prometheus text format.
+pub fn prometheus() -> String {
+    __inner::prometheus(&__inner::registry())
+}
+
+/// An owned copy of every series in a metric set.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    /// Name of the metric set.
+    /// Includes the module path when another set has the same name.
+    pub set: String,
+    /// Every series of every metric in the set.
+    pub series: Vec<SeriesValue>,
+}
+
+/// The value of one series in a [`Snapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeriesValue {
+    /// Name of the metric.
+    pub metric: String,
+    /// Label keys and values of the series.
+    pub labels: Vec<(String, String)>,
+    /// Value of the series.
+    pub value: Value,
+}
+
+impl Snapshot {
+    fn new(set: &str, values: impl Iterator<Item = (&'static str, Labels, Value)>) -> Self {
+        let series = values
+            .map(|(metric, labels, value)| SeriesValue {
+                metric: metric.to_string(),
+                labels: labels
+                    .0
+                    .into_iter()
+                    .map(|(k, v)| (k.to_string(), v))
+                    .collect(),
+                value,
+            })
+            .collect();
+        Self {
+            set: set.to_string(),
+            series,
+        }
+    }
+
+    /// Get the value of a series.
+    /// The label values are in the same order as the label keys
+    /// and are empty for the series without labels.
+    pub fn value(&self, metric: &str, labels: &[&str]) -> Option<&Value> {
+        self.series
+            .iter()
+            .find(|s| s.metric == metric && s.labels.iter().map(|(_, v)| v).eq(labels.iter()))
+            .map(|s| &s.value)
+    }
+
+    /// What happened since an earlier snapshot of the same set.
+    /// Counters and histograms are the difference and gauges
+    /// are their current value.
+    /// Series that are not in `since` are kept as they are.
+    pub fn delta(&self, since: &Snapshot) -> Snapshot {
+        let series = self
+            .series
+            .iter()
+            .map(|s| {
+                let before = since
+                    .series
+                    .iter()
+                    .find(|b| b.metric == s.metric && b.labels == s.labels)
+                    .map(|b| &b.value);
+                let value = match (&s.value, before) {
+                    (Value::Counter(n), Some(Value::Counter(b))) => {
+                        Value::Counter(n.saturating_sub(*b))
+                    }
+                    (Value::Histogram(h), Some(Value::Histogram(b))) => {
+                        Value::Histogram(Histogram {
+                            count: h.count.saturating_sub(b.count),
+                            sum: h.sum.saturating_sub(b.sum),
+                            buckets: h
+                                .buckets
+                                .iter()
+                                .zip(&b.buckets)
+                                .map(|(n, b)| n.saturating_sub(*b))
+                                .collect(),
+                        })
+                    }
+                    (value, _) => value.clone(),
+                };
+                SeriesValue { value, ..s.clone() }
+            })
+            .collect();
+        Snapshot {
+            set: self.set.clone(),
+            series,
+        }
+    }
+}
+
+/// Emit tracing events for every metric
+/// in every registered set.
+pub fn print_all() {
+    for set in __inner::registry() {
+        set.print();
+    }
+}
+
+/// Get a snapshot of every registered set.
+/// Sets are in name order.
+pub fn snapshot_all() -> Vec<Snapshot> {
+    __inner::registry()
+        .into_iter()
+        .map(|set| set.snapshot())
+        .collect()
+}
+
+/// Save every registered metric set to one csv.
+/// Columns are named `Set::Metric{labels}`.
+pub fn save_all_csv(path: &std::path::Path) {
+    if is_enabled() {
+        let mut keys = String::new();
+        let mut values = String::new();
+        for set in __inner::registry() {
+            set.write_csv(Some(&set.display_name()), &mut keys, &mut values);
+        }
+        std::fs::write(path, format!("{}\n{}\n", keys, values))
+            .expect("Failed to write metrics to csv");
+        tracing::info!(metrics = "Saved csv to", ?path);
+    }
+}
+
 #[allow(missing_docs)]
 #[doc(hidden)]
 pub mod __inner {
-    use super::METRICS_ON;
-    use std::sync::atomic::AtomicU64;
-    pub fn count_silent<N, E>(metrics: &[AtomicU64], metric: usize, n: N) -> u64
-    where
-        E: std::fmt::Debug,
-        std::num::TryFromIntError: From<E>,
-        N: std::convert::TryInto<u64, Error = E>,
-    {
-        if METRICS_ON.load(std::sync::atomic::Ordering::Relaxed) {
-            let n = n.try_into().expect("Failed to convert metric to u64");
-            let mut last = metrics[metric].fetch_add(n, std::sync::atomic::Ordering::Relaxed);
-            last += n;
-            last
-        } else {
-            0
+    pub use super::prometheus::render as prometheus;
+    use super::{
+        Histogram, Kind, Labels, Series, Snapshot, Value, BUCKETS, LABEL_CAP, METRICS_ON,
+        OVERFLOW_LABEL,
+    };
+    pub use ctor::ctor;
+    use std::borrow::Cow;
+    use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    /// Storage for one metric.
+    /// Counters and histogram sums use `value`.
+    /// Gauges store their `i64` bits in `value`.
+    /// Each label combination gets its own slot in `series`.
+    pub struct Slot {
+        value: AtomicU64,
+        count: AtomicU64,
+        buckets: [AtomicU64; BUCKETS],
+        series: Mutex<Vec<LabeledSlot>>,
+    }
+
+    /// The label values and storage of one series.
+    type LabeledSlot = (Box<[String]>, Arc<Slot>);
+
+    #[allow(clippy::declare_interior_mutable_const)]
+    const ZERO: AtomicU64 = AtomicU64::new(0);
+
+    impl Slot {
+        #[allow(clippy::new_without_default)]
+        pub const fn new() -> Self {
+            Self {
+                value: ZERO,
+                count: ZERO,
+                buckets: [ZERO; BUCKETS],
+                series: Mutex::new(Vec::new()),
+            }
         }
     }
-    pub fn count<N, E>(metrics: &[AtomicU64], names: &[&str], metric: usize, n: N, filter: &str)
-    where
-        E: std::fmt::Debug,
-        std::num::TryFromIntError: From<E>,
-        N: std::convert::TryInto<u64, Error = E>,
-    {
-        if METRICS_ON.load(std::sync::atomic::Ordering::Relaxed) {
-            let n = n.try_into().expect("Failed to convert metric to u64");
-            let r = count_silent::<_, std::convert::Infallible>(metrics, metric, n);
-            let name = names[metric];
-            let span = tracing::trace_span!("metric_count", %filter, %name);
-            span.in_scope(|| {
-                tracing::trace!(metric = %name, count = r, change = n);
-            });
-        }
-    }
-    pub fn get(metrics: &[AtomicU64], metric: usize) -> u64 {
-        if METRICS_ON.load(std::sync::atomic::Ordering::Relaxed) {
-            metrics[metric].load(std::sync::atomic::Ordering::Relaxed)
+
+    /// Everything the macro generates for a metric set.
+    pub struct Set {
+        pub name: &'static str,
+        /// Module the set is declared in.
+        pub path: &'static str,
+        /// Another registered set has the same name.
+        pub shares_name: AtomicBool,
+        pub metrics: &'static [Slot],
+        pub names: &'static [&'static str],
+        pub kinds: &'static [Kind],
+        pub labels: &'static [&'static [&'static str]],
+        pub docs: &'static [&'static [&'static str]],
+        /// Nanoseconds since the epoch of the last reset
+        /// or zero if the set was never reset.
+        pub reset_at: AtomicU64,
+    }
+
+    fn is_on() -> bool {
+        METRICS_ON.load(Ordering::Relaxed)
+    }
+
+    /// Every declared set.
+    static REGISTRY: Mutex<Vec<&'static Set>> = Mutex::new(Vec::new());
+
+    /// All registered sets sorted by name.
+    pub(super) fn registry() -> Vec<&'static Set> {
+        let mut sets = REGISTRY
+            .lock()
+            .expect("Metrics registry lock poisoned")
+            .clone();
+        sets.sort_by_key(|s| s.display_name());
+        sets
+    }
+
+    pub(super) fn slot_count(slot: &Slot, n: u64) -> u64 {
+        if is_on() {
+            slot.value.fetch_add(n, Ordering::Relaxed) + n
         } else {
             0
         }
     }
-    pub fn iter(
-        metrics: &'static [AtomicU64],
-        names: &'static [&'static str],
-    ) -> impl Iterator<Item = (&'static str, u64)> {
-        metrics
+    pub(super) fn slot_set(slot: &Slot, value: i64) {
+        if is_on() {
+            slot.value.store(value as u64, Ordering::Relaxed);
+        }
+    }
+    pub(super) fn slot_add(slot: &Slot, n: i64) {
+        if is_on() {
+            // Two's complement so adding the bits wraps correctly.
+            slot.value.fetch_add(n as u64, Ordering::Relaxed);
+        }
+    }
+    pub(super) fn slot_record(slot: &Slot, value: u64) {
+        if is_on() {
+            slot.buckets[Histogram::bucket(value)].fetch_add(1, Ordering::Relaxed);
+            slot.count.fetch_add(1, Ordering::Relaxed);
+            slot.value.fetch_add(value, Ordering::Relaxed);
+        }
+    }
+    pub(super) fn load(slot: &Slot, kind: Kind) -> Value {
+        let value = slot.value.load(Ordering::Relaxed);
+        match kind {
+            Kind::Counter => Value::Counter(value),
+            Kind::Gauge => Value::Gauge(value as i64),
+            Kind::Histogram => Value::Histogram(Histogram {
+                count: slot.count.load(Ordering::Relaxed),
+                sum: value,
+                buckets: slot
+                    .buckets
+                    .iter()
+                    .map(|b| b.load(Ordering::Relaxed))
+                    .collect(),
+            }),
+        }
+    }
+    /// Like `load` but sets counters and histograms back to zero.
+    /// Gauges are left as they are.
+    pub(super) fn swap(slot: &Slot, kind: Kind) -> Value {
+        match kind {
+            Kind::Counter => Value::Counter(slot.value.swap(0, Ordering::Relaxed)),
+            Kind::Gauge => load(slot, kind),
+            Kind::Histogram => Value::Histogram(Histogram {
+                count: slot.count.swap(0, Ordering::Relaxed),
+                sum: slot.value.swap(0, Ordering::Relaxed),
+                buckets: slot
+                    .buckets
+                    .iter()
+                    .map(|b| b.swap(0, Ordering::Relaxed))
+                    .collect(),
+            }),
+        }
+    }
+    fn to_u64(value: &Value) -> u64 {
+        match value {
+            Value::Counter(n) => *n,
+            Value::Gauge(n) => (*n).max(0) as u64,
+            Value::Histogram(h) => h.count,
+        }
+    }
+    /// Keys are written like `Sent{peer=a;zome=b}` so
+    /// they don't break the csv.
+    pub(super) fn csv_key(name: &str, labels: &Labels) -> String {
+        if labels.is_empty() {
+            return name.to_string();
+        }
+        let labels = labels
+            .0
             .iter()
-            .zip(names.iter())
-            .map(|(i, &name)| (name, i.load(std::sync::atomic::Ordering::Relaxed)))
-    }
-    pub fn print(metrics: &[AtomicU64], names: &[&str]) {
-        if METRICS_ON.load(std::sync::atomic::Ordering::Relaxed) {
-            let span = tracing::trace_span!("print_metrics");
-            for (i, count) in metrics.iter().enumerate() {
-                let metric = names[i];
-                let count = count.load(std::sync::atomic::Ordering::Relaxed);
+            .map(|(k, v)| format!("{}={}", k, v.replace([',', '\n'], "_")))
+            .collect::<Vec<_>>()
+            .join(";");
+        format!("{}{{{}}}", name, labels)
+    }
+    /// Append the columns of one metric to a csv header and row.
+    /// Histograms get a column for the count, sum, p50 and p99.
+    pub(super) fn write_csv_value(
+        key: &str,
+        value: &Value,
+        keys: &mut String,
+        values: &mut String,
+    ) {
+        use std::fmt::Write;
+        match value {
+            Value::Counter(count) => {
+                write!(keys, "{},", key).expect("Failed to write metrics");
+                write!(values, "{},", count).expect("Failed to write metrics");
+            }
+            Value::Gauge(value) => {
+                write!(keys, "{},", key).expect("Failed to write metrics");
+                write!(values, "{},", value).expect("Failed to write metrics");
+            }
+            Value::Histogram(h) => {
+                write!(keys, "{0}_count,{0}_sum,{0}_p50,{0}_p99,", key)
+                    .expect("Failed to write metrics");
+                write!(
+                    values,
+                    "{},{},{},{},",
+                    h.count,
+                    h.sum,
+                    h.quantile(0.5),
+                    h.quantile(0.99)
+                )
+                .expect("Failed to write metrics");
+            }
+        }
+    }
+
+    impl Set {
+        fn to_u64<N, E>(n: N) -> u64
+        where
+            E: std::fmt::Debug,
+            std::num::TryFromIntError: From<E>,
+            N: std::convert::TryInto<u64, Error = E>,
+        {
+            n.try_into().expect("Failed to convert metric to u64")
+        }
+        /// The doc comment of a metric on one line
+        /// or `Set::Metric` if it has none.
+        pub fn help(&self, metric: &str) -> String {
+            let docs = self
+                .names
+                .iter()
+                .position(|n| *n == metric)
+                .map(|i| self.docs[i])
+                .unwrap_or_default();
+            if docs.is_empty() {
+                format!("{}::{}", self.name, metric)
+            } else {
+                docs.iter().map(|l| l.trim()).collect::<Vec<_>>().join(" ")
+            }
+        }
+        /// Add this set to the registry.
+        /// Called once for each set before main.
+        /// Sets with the same name are all given their
+        /// module path so their metrics don't collide.
+        pub fn register(&'static self) {
+            let mut registry = REGISTRY.lock().expect("Metrics registry lock poisoned");
+            for set in registry.iter().filter(|s| s.name == self.name) {
+                set.shares_name.store(true, Ordering::Relaxed);
+                self.shares_name.store(true, Ordering::Relaxed);
+            }
+            registry.push(self);
+        }
+        /// The name the set is shown with.
+        /// This is `path::Name` if another set
+        /// has the same name or `Name` otherwise.
+        pub fn display_name(&self) -> Cow<'static, str> {
+            if self.shares_name.load(Ordering::Relaxed) {
+                format!("{}::{}", self.path, self.name).into()
+            } else {
+                self.name.into()
+            }
+        }
+        pub fn count_silent<N, E>(&'static self, metric: usize, n: N) -> u64
+        where
+            E: std::fmt::Debug,
+            std::num::TryFromIntError: From<E>,
+            N: std::convert::TryInto<u64, Error = E>,
+        {
+            if is_on() {
+                slot_count(&self.metrics[metric], Self::to_u64(n))
+            } else {
+                0
+            }
+        }
+        pub fn count<N, E>(&'static self, metric: usize, n: N, filter: &str)
+        where
+            E: std::fmt::Debug,
+            std::num::TryFromIntError: From<E>,
+            N: std::convert::TryInto<u64, Error = E>,
+        {
+            if is_on() {
+                let n = Self::to_u64(n);
+                let r = slot_count(&self.metrics[metric], n);
+                let name = self.names[metric];
+                let span = tracing::trace_span!("metric_count", %filter, %name);
                 span.in_scope(|| {
-                    tracing::trace!(%metric, count);
+                    tracing::trace!(metric = %name, count = r, change = n);
                 });
             }
         }
-    }
-    pub fn save_csv(metrics: &[AtomicU64], names: &[&str], path: &std::path::Path) {
-        if METRICS_ON.load(std::sync::atomic::Ordering::Relaxed) {
-            use std::fmt::Write;
-            let mut keys = String::new();
-            let mut values = String::new();
-            for (count, metric) in metrics.iter().zip(names.iter()) {
-                let count = count.load(std::sync::atomic::Ordering::Relaxed);
-                write!(keys, "{},", metric).expect("Failed to write metrics");
-                write!(values, "{},", count).expect("Failed to write metrics");
+        pub fn set(&'static self, metric: usize, value: i64) {
+            debug_assert_eq!(self.kinds[metric], Kind::Gauge, "Only gauges can be set");
+            slot_set(&self.metrics[metric], value);
+        }
+        pub fn add(&'static self, metric: usize, n: i64) {
+            debug_assert_eq!(self.kinds[metric], Kind::Gauge, "Only gauges can go down");
+            slot_add(&self.metrics[metric], n);
+        }
+        pub fn record<N, E>(&'static self, metric: usize, value: N)
+        where
+            E: std::fmt::Debug,
+            std::num::TryFromIntError: From<E>,
+            N: std::convert::TryInto<u64, Error = E>,
+        {
+            debug_assert_eq!(
+                self.kinds[metric],
+                Kind::Histogram,
+                "Only histograms can record"
+            );
+            if is_on() {
+                slot_record(&self.metrics[metric], Self::to_u64(value));
+            }
+        }
+        pub fn with_labels(&'static self, metric: usize, values: &[&str]) -> Series {
+            debug_assert_eq!(
+                values.len(),
+                self.labels[metric].len(),
+                "Wrong number of label values for {}",
+                self.names[metric]
+            );
+            let kind = self.kinds[metric];
+            // The series is tracked even while metrics are off so
+            // it still counts if they are turned on later.
+            let mut series = self.metrics[metric]
+                .series
+                .lock()
+                .expect("Metrics series lock poisoned");
+            let find = |series: &[LabeledSlot], values: &[&str]| {
+                series
+                    .iter()
+                    .find(|(v, _)| v.iter().map(String::as_str).eq(values.iter().copied()))
+                    .map(|(_, slot)| slot.clone())
+            };
+            if let Some(slot) = find(&series, values) {
+                return Series { slot, kind };
+            }
+            let overflow = vec![OVERFLOW_LABEL; values.len()];
+            let values = if series.len() < LABEL_CAP.load(Ordering::Relaxed) {
+                values
+            } else if let Some(slot) = find(&series, &overflow) {
+                return Series { slot, kind };
+            } else {
+                &overflow[..]
+            };
+            let slot = Arc::new(Slot::new());
+            series.push((values.iter().map(|v| v.to_string()).collect(), slot.clone()));
+            Series { slot, kind }
+        }
+        pub fn get(&self, metric: usize) -> u64 {
+            to_u64(&self.value(metric))
+        }
+        pub fn value(&self, metric: usize) -> Value {
+            if is_on() {
+                load(&self.metrics[metric], self.kinds[metric])
+            } else {
+                load(&Slot::new(), self.kinds[metric])
+            }
+        }
+        pub fn iter(&'static self) -> impl Iterator<Item = (&'static str, Labels, u64)> {
+            self.values().map(|(name, l, v)| (name, l, to_u64(&v)))
+        }
+        /// Every metric followed by each of its label combinations.
+        pub fn values(&'static self) -> impl Iterator<Item = (&'static str, Labels, Value)> {
+            self.read(load)
+        }
+        pub fn snapshot(&'static self) -> Snapshot {
+            Snapshot::new(&self.display_name(), self.values())
+        }
+        pub fn reset(&'static self) -> Snapshot {
+            let now = std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default();
+            self.reset_at.store(now, Ordering::Relaxed);
+            Snapshot::new(&self.display_name(), self.read(swap))
+        }
+        fn read(
+            &'static self,
+            read: fn(&Slot, Kind) -> Value,
+        ) -> impl Iterator<Item = (&'static str, Labels, Value)> {
+            let mut values = Vec::new();
+            for (i, slot) in self.metrics.iter().enumerate() {
+                let (name, kind) = (self.names[i], self.kinds[i]);
+                values.push((name, Labels::default(), read(slot, kind)));
+                let series = slot.series.lock().expect("Metrics series lock poisoned");
+                for (label_values, slot) in series.iter() {
+                    let labels = self.labels[i]
+                        .iter()
+                        .copied()
+                        .zip(label_values.iter().cloned())
+                        .collect();
+                    values.push((name, Labels(labels), read(slot, kind)));
+                }
+            }
+            values.into_iter()
+        }
+        pub fn print(&'static self) {
+            if is_on() {
+                let span = tracing::trace_span!("print_metrics", set = %self.display_name());
+                for (metric, labels, value) in self.values() {
+                    span.in_scope(|| match value {
+                        Value::Counter(count) => tracing::trace!(%metric, %labels, count),
+                        Value::Gauge(value) => tracing::trace!(%metric, %labels, value),
+                        Value::Histogram(h) => tracing::trace!(
+                            %metric,
+                            %labels,
+                            count = h.count,
+                            sum = h.sum,
+                            p50 = h.quantile(0.5),
+                            p99 = h.quantile(0.99)
+                        ),
+                    });
+                }
+            }
+        }
+        pub fn save_csv(&'static self, path: &std::path::Path) {
+            if is_on() {
+                let mut keys = String::new();
+                let mut values = String::new();
+                self.write_csv(None, &mut keys, &mut values);
+                std::fs::write(path, format!("{}\n{}\n", keys, values))
+                    .expect("Failed to write metrics to csv");
+                tracing::info!(metrics = "Saved csv to", ?path);
+            }
+        }
+        /// Append this sets columns to a csv header and row.
+        /// Keys are prefixed with `{prefix}::` when there is one.
+        pub(super) fn write_csv(
+            &'static self,
+            prefix: Option<&str>,
+            keys: &mut String,
+            values: &mut String,
+        ) {
+            for (metric, labels, value) in self.values() {
+                let mut metric = csv_key(metric, &labels);
+                if let Some(prefix) = prefix {
+                    metric = format!("{}::{}", prefix, metric);
+                }
+                write_csv_value(&metric, &value, keys, values);
             }
-            std::fs::write(path, format!("{}\n{}\n", keys, values))
-                .expect("Failed to write metrics to csv");
-            tracing::info!(metrics = "Saved csv to", ?path);
         }
     }
 }
@@ -0,0 +1,275 @@
+//! OpenTelemetry metrics export of metric sets.
+//! Metrics are sent as OTLP/HTTP JSON with each set as the
+//! instrument scope and the tracer's resource attributes.
+use super::{
+    __inner::Set,
+    periodic::{self, Periodic},
+    prometheus::snake_case,
+    Histogram, Labels, Value, BUCKETS,
+};
+use once_cell::sync::Lazy;
+use serde_json::{json, Value as Json};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DEFAULT_PORT: u16 = 4318;
+const DEFAULT_PATH: &str = "/v1/metrics";
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// Cumulative temporality in OTLP.
+const CUMULATIVE: u8 = 2;
+
+/// Counters and histograms are cumulative since this time.
+/// Set when metrics are turned on.
+static START: Lazy<u128> = Lazy::new(now);
+
+/// Mark the time the metrics started.
+pub(super) fn start() {
+    Lazy::force(&START);
+}
+
+fn now() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or_default()
+}
+
+/// An `http://host[:port][/path]` collector endpoint.
+struct Endpoint {
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(endpoint: &str) -> io::Result<Self> {
+        if endpoint.starts_with("https://") {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "https OTLP endpoints are not supported, use an http collector: {}",
+                    endpoint
+                ),
+            ));
+        }
+        let rest = endpoint.strip_prefix("http://").ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Only http OTLP endpoints are supported: {}", endpoint),
+            )
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) if i + 1 < rest.len() => (&rest[..i], &rest[i..]),
+            Some(i) => (&rest[..i], DEFAULT_PATH),
+            None => (rest, DEFAULT_PATH),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) => (
+                host,
+                port.parse().map_err(|_| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Invalid OTLP endpoint port: {}", endpoint),
+                    )
+                })?,
+            ),
+            None => (authority, DEFAULT_PORT),
+        };
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+        })
+    }
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last = None;
+        for addr in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last = Some(e),
+            }
+        }
+        Err(last.unwrap_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("OTLP endpoint has no addresses: {}", self.host),
+            )
+        }))
+    }
+
+    fn post(&self, body: &str) -> io::Result<()> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.set_write_timeout(Some(TIMEOUT))?;
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            self.path,
+            self.host,
+            self.port,
+            body.len(),
+            body
+        )?;
+        let mut reader = BufReader::new(&stream);
+        let mut status = String::new();
+        reader.read_line(&mut status)?;
+        // Read the whole response so closing doesn't reset the connection.
+        let mut len = None;
+        let mut header = String::new();
+        while reader.read_line(&mut header)? > 2 {
+            if let Some(n) = header.to_lowercase().strip_prefix("content-length:") {
+                len = n.trim().parse().ok();
+            }
+            header.clear();
+        }
+        match len {
+            Some(len) => io::copy(&mut reader.take(len), &mut io::sink())?,
+            None => io::copy(&mut reader, &mut io::sink())?,
+        };
+        match status.split_whitespace().nth(1) {
+            Some(code) if code.starts_with('2') => Ok(()),
+            _ => Err(io::Error::other(format!(
+                "OTLP collector rejected metrics: {}",
+                status.trim()
+            ))),
+        }
+    }
+}
+
+fn any_value(value: &opentelemetry::api::Value) -> Json {
+    use opentelemetry::api::Value as V;
+    match value {
+        V::Bool(b) => json!({ "boolValue": b }),
+        V::I64(n) => json!({ "intValue": n.to_string() }),
+        V::U64(n) => json!({ "intValue": n.to_string() }),
+        V::F64(n) => json!({ "doubleValue": n }),
+        v => json!({ "stringValue": String::from(v) }),
+    }
+}
+
+fn attributes(labels: &Labels) -> Json {
+    labels
+        .0
+        .iter()
+        .map(|(k, v)| json!({ "key": k, "value": { "stringValue": v } }))
+        .collect()
+}
+
+fn histogram_point(labels: &Labels, h: &Histogram, start: &str, time: &str) -> Json {
+    json!({
+        "attributes": attributes(labels),
+        "startTimeUnixNano": start,
+        "timeUnixNano": time,
+        "count": h.count.to_string(),
+        "sum": h.sum as f64,
+        "bucketCounts": h.buckets.iter().map(u64::to_string).collect::<Vec<_>>(),
+        // The last bucket has no upper bound.
+        "explicitBounds": (0..BUCKETS - 1)
+            .map(|i| Histogram::bucket_bound(i) as f64)
+            .collect::<Vec<_>>(),
+    })
+}
+
+/// One scope with every metric in the set.
+fn scope(set: &'static Set, time: &str) -> Json {
+    // Counters and histograms start again when the set is reset.
+    let start = match set.reset_at.load(Ordering::Relaxed) {
+        0 => START.to_string(),
+        reset_at => reset_at.to_string(),
+    };
+    let start = start.as_str();
+    let mut metrics: Vec<(&str, &str, Json, Vec<Json>)> = Vec::new();
+    for (metric, labels, value) in set.values() {
+        let point = match &value {
+            Value::Counter(n) => json!({
+                "attributes": attributes(&labels),
+                "startTimeUnixNano": start,
+                "timeUnixNano": time,
+                "asInt": n.to_string(),
+            }),
+            Value::Gauge(n) => json!({
+                "attributes": attributes(&labels),
+                "timeUnixNano": time,
+                "asInt": n.to_string(),
+            }),
+            Value::Histogram(h) => histogram_point(&labels, h, start, time),
+        };
+        match metrics.last_mut() {
+            Some((last, _, _, points)) if *last == metric => points.push(point),
+            _ => {
+                let (kind, data) = match value {
+                    Value::Counter(_) => (
+                        "sum",
+                        json!({ "aggregationTemporality": CUMULATIVE, "isMonotonic": true }),
+                    ),
+                    Value::Gauge(_) => ("gauge", json!({})),
+                    Value::Histogram(_) => {
+                        ("histogram", json!({ "aggregationTemporality": CUMULATIVE }))
+                    }
+                };
+                metrics.push((metric, kind, data, vec![point]));
+            }
+        }
+    }
+    let metrics: Vec<_> = metrics
+        .into_iter()
+        .map(|(metric, kind, mut data, points)| {
+            data["dataPoints"] = Json::Array(points);
+            json!({
+                "name": snake_case(metric),
+                "description": set.help(metric),
+                kind: data,
+            })
+        })
+        .collect();
+    json!({
+        "scope": { "name": set.display_name() },
+        "metrics": metrics,
+    })
+}
+
+/// Render the sets as an OTLP metrics request.
+pub(super) fn render(sets: &[&'static Set]) -> Json {
+    let time = now().to_string();
+    let resource: Vec<_> = crate::open::resource()
+        .iter()
+        .map(|kv| json!({ "key": kv.key.as_str(), "value": any_value(&kv.value) }))
+        .collect();
+    json!({
+        "resourceMetrics": [{
+            "resource": { "attributes": resource },
+            "scopeMetrics": sets.iter().map(|set| scope(set, &time)).collect::<Vec<_>>(),
+        }]
+    })
+}
+
+/// Send every registered metric set to an OTLP/HTTP collector
+/// at `http://host[:port][/path]`.
+/// The port defaults to 4318 and the path to `/v1/metrics`.
+pub fn export_otlp(endpoint: &str) -> io::Result<()> {
+    Endpoint::parse(endpoint)?.post(&render(&super::__inner::registry()).to_string())
+}
+
+/// Export every registered metric set to an OTLP/HTTP collector
+/// each `interval` from a background thread.
+/// See [`export_otlp`] for the endpoint format.
+pub fn start_otlp_export(endpoint: &str, interval: Duration) -> io::Result<OtlpExport> {
+    let endpoint = Endpoint::parse(endpoint)?;
+    let worker = periodic::spawn("metrics-otlp", interval, move || {
+        let body = render(&super::__inner::registry()).to_string();
+        if let Err(e) = endpoint.post(&body) {
+            tracing::warn!(msg = "Failed to export metrics", error = %e);
+        }
+    })?;
+    Ok(OtlpExport { _worker: worker })
+}
+
+/// Periodic OTLP export of the metric sets.
+/// Dropping this sends the metrics one last
+/// time and stops the export.
+pub struct OtlpExport {
+    _worker: Periodic,
+}
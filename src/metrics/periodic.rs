@@ -0,0 +1,43 @@
+//! A background thread that runs a task on an interval.
+use std::io;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// Runs the task each interval until dropped.
+/// Dropping runs the task one last time
+/// and waits for the thread to finish.
+pub(super) struct Periodic {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+/// Spawn a thread called `name` that calls `run` each `interval`.
+pub(super) fn spawn<F>(name: &str, interval: Duration, mut run: F) -> io::Result<Periodic>
+where
+    F: FnMut() + Send + 'static,
+{
+    let (stop, rx) = mpsc::channel::<()>();
+    let thread = std::thread::Builder::new()
+        .name(name.into())
+        .spawn(move || loop {
+            let done = !matches!(rx.recv_timeout(interval), Err(RecvTimeoutError::Timeout));
+            run();
+            if done {
+                break;
+            }
+        })?;
+    Ok(Periodic {
+        stop: Some(stop),
+        thread: Some(thread),
+    })
+}
+
+impl Drop for Periodic {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
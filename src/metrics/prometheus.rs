@@ -0,0 +1,192 @@
+//! Prometheus text exposition of metric sets.
+use super::{__inner::Set, Histogram, Labels, Value, BUCKETS};
+use std::fmt::Write;
+#[cfg(feature = "metrics-endpoint")]
+use std::{
+    net::SocketAddr,
+    sync::atomic::{AtomicBool, Ordering},
+    sync::Arc,
+    thread::JoinHandle,
+    time::Duration,
+};
+
+#[cfg(feature = "metrics-endpoint")]
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// How often the endpoint checks for new connections.
+#[cfg(feature = "metrics-endpoint")]
+const POLL: Duration = Duration::from_millis(50);
+
+/// `MyMetric` -> `my_metric` and `my_mod::MySet` -> `my_mod_my_set`
+pub(super) fn snake_case(name: &str) -> String {
+    let name = name.replace("::", "_");
+    let mut out = String::with_capacity(name.len() + 4);
+    for (i, c) in name.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 && !out.ends_with('_') {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// The labels with an extra `le` label for histogram buckets.
+fn with_le(labels: &Labels, le: String) -> Labels {
+    let mut labels = labels.clone();
+    labels.0.push(("le", le));
+    labels
+}
+
+/// Render the sets in the Prometheus text format.
+/// Metrics are named `{set}_{metric}` in snake case and
+/// counters get the `_total` suffix.
+pub fn render(sets: &[&'static Set]) -> String {
+    let mut out = String::new();
+    for set in sets {
+        let prefix = snake_case(&set.display_name());
+        let mut last = None;
+        for (metric, labels, value) in set.values() {
+            let name = format!("{}_{}", prefix, snake_case(metric));
+            if last != Some(metric) {
+                let (suffix, kind) = match value {
+                    Value::Counter(_) => ("_total", "counter"),
+                    Value::Gauge(_) => ("", "gauge"),
+                    Value::Histogram(_) => ("", "histogram"),
+                };
+                let help = set.help(metric).replace('\\', "\\\\");
+                writeln!(out, "# HELP {}{} {}", name, suffix, help)
+                    .expect("Failed to write metrics");
+                writeln!(out, "# TYPE {}{} {}", name, suffix, kind)
+                    .expect("Failed to write metrics");
+                last = Some(metric);
+            }
+            match value {
+                Value::Counter(n) => writeln!(out, "{}_total{} {}", name, labels, n),
+                Value::Gauge(n) => writeln!(out, "{}{} {}", name, labels, n),
+                Value::Histogram(h) => render_histogram(&mut out, &name, &labels, &h),
+            }
+            .expect("Failed to write metrics");
+        }
+    }
+    out
+}
+
+fn render_histogram(
+    out: &mut String,
+    name: &str,
+    labels: &Labels,
+    h: &Histogram,
+) -> std::fmt::Result {
+    let mut cumulative = 0;
+    // The last bucket goes up to u64::MAX so it is `+Inf`.
+    for (i, n) in h.buckets.iter().enumerate().take(BUCKETS - 1) {
+        cumulative += n;
+        let le = with_le(labels, Histogram::bucket_bound(i).to_string());
+        writeln!(out, "{}_bucket{} {}", name, le, cumulative)?;
+    }
+    writeln!(
+        out,
+        "{}_bucket{} {}",
+        name,
+        with_le(labels, "+Inf".into()),
+        h.count
+    )?;
+    writeln!(out, "{}_sum{} {}", name, labels, h.sum)?;
+    writeln!(out, "{}_count{} {}", name, labels, h.count)
+}
+
+/// Serve every registered metric set on `http://127.0.0.1:{port}/metrics`
+/// from a background thread. Use port 0 to pick a free port.
+/// Dropping the returned server stops serving and frees the port.
+#[cfg(feature = "metrics-endpoint")]
+pub fn serve_prometheus(port: u16) -> std::io::Result<PrometheusServer> {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let addr = listener.local_addr()?;
+    // Accept doesn't block so the thread can see the stop.
+    listener.set_nonblocking(true)?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let thread = std::thread::Builder::new()
+        .name("metrics-endpoint".into())
+        .spawn(move || {
+            while !stopped.load(Ordering::Acquire) {
+                let mut stream = match listener.accept() {
+                    Ok((s, _)) => s,
+                    Err(_) => {
+                        std::thread::sleep(POLL);
+                        continue;
+                    }
+                };
+                // A slow client can't hold up the other scrapes for long.
+                if stream.set_nonblocking(false).is_err()
+                    || stream.set_read_timeout(Some(TIMEOUT)).is_err()
+                    || stream.set_write_timeout(Some(TIMEOUT)).is_err()
+                {
+                    continue;
+                }
+                let mut reader = BufReader::new(&stream);
+                let mut request = String::new();
+                if reader.read_line(&mut request).is_err() {
+                    continue;
+                }
+                // Read the rest of the headers so closing doesn't reset the connection.
+                let mut header = String::new();
+                while matches!(reader.read_line(&mut header), Ok(n) if n > 2) {
+                    header.clear();
+                }
+                let response = if request.starts_with("GET /metrics ") {
+                    let body = super::prometheus();
+                    format!(
+                        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                        body.len(),
+                        body
+                    )
+                } else {
+                    "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                        .to_string()
+                };
+                if let Err(e) = stream.write_all(response.as_bytes()) {
+                    tracing::warn!(msg = "Failed to serve metrics", error = %e);
+                }
+            }
+        })?;
+    Ok(PrometheusServer {
+        addr,
+        stop,
+        thread: Some(thread),
+    })
+}
+
+/// The Prometheus endpoint.
+/// Dropping this stops serving and frees the port.
+#[cfg(feature = "metrics-endpoint")]
+pub struct PrometheusServer {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+#[cfg(feature = "metrics-endpoint")]
+impl PrometheusServer {
+    /// The address being served.
+    pub fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+}
+
+#[cfg(feature = "metrics-endpoint")]
+impl Drop for PrometheusServer {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Release);
+        // The listener is closed when the thread ends.
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
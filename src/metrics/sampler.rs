@@ -0,0 +1,88 @@
+//! Periodic sampling of metric sets to a time series csv.
+use super::{
+    __inner::{csv_key, registry, write_csv_value},
+    periodic::{self, Periodic},
+    Value,
+};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+/// Counter totals from the last row so
+/// the next row can show the rate.
+struct Sampler {
+    file: File,
+    header: String,
+    last: Option<(Instant, HashMap<String, u64>)>,
+}
+
+impl Sampler {
+    fn sample(&mut self) -> io::Result<()> {
+        use std::fmt::Write;
+        let now = Instant::now();
+        let mut keys = String::from("time,");
+        let mut values = chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Millis, true);
+        values.push(',');
+        let mut counters = HashMap::new();
+        for set in registry() {
+            for (metric, labels, value) in set.values() {
+                let key = format!("{}::{}", set.display_name(), csv_key(metric, &labels));
+                write_csv_value(&key, &value, &mut keys, &mut values);
+                if let Value::Counter(count) = value {
+                    write!(keys, "{}_per_sec,", key).expect("Failed to write metrics");
+                    // The first row has nothing to compare to.
+                    if let Some((then, last)) = &self.last {
+                        let secs = now.duration_since(*then).as_secs_f64();
+                        let delta = count.saturating_sub(last.get(&key).copied().unwrap_or(0));
+                        if secs > 0.0 {
+                            write!(values, "{:.3}", delta as f64 / secs)
+                                .expect("Failed to write metrics");
+                        }
+                    }
+                    values.push(',');
+                    counters.insert(key, count);
+                }
+            }
+        }
+        // New sets and label values add columns
+        // so the header is written again.
+        if keys != self.header {
+            writeln!(self.file, "{}", keys)?;
+            self.header = keys;
+        }
+        writeln!(self.file, "{}", values)?;
+        self.file.flush()?;
+        self.last = Some((now, counters));
+        Ok(())
+    }
+}
+
+/// Append a timestamped row of every registered metric set
+/// to a csv each `interval` from a background thread.
+/// Counters also get a `_per_sec` column with their rate
+/// since the last row.
+/// The header is written again whenever the columns change.
+pub fn sample_csv(path: &Path, interval: Duration) -> io::Result<CsvSampler> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut sampler = Sampler {
+        file,
+        header: String::new(),
+        last: None,
+    };
+    sampler.sample()?;
+    let worker = periodic::spawn("metrics-csv", interval, move || {
+        if let Err(e) = sampler.sample() {
+            tracing::warn!(msg = "Failed to sample metrics to csv", error = %e);
+        }
+    })?;
+    Ok(CsvSampler { _worker: worker })
+}
+
+/// Periodic sampling of the metric sets to a csv.
+/// Dropping this writes one last row
+/// and stops the sampling.
+pub struct CsvSampler {
+    _worker: Periodic,
+}
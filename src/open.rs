@@ -3,12 +3,29 @@ pub use off::*;
 #[cfg(feature = "opentelemetry-on")]
 pub use on::*;
 
+use crate::errors::{TracingError, WireError};
+
+pub use config::Config;
 pub use context_wrap::MsgWrap;
+pub use future_ext::{FutureExt, InContext};
+pub use headers::{HeaderExtractor, HeaderInjector, Propagator};
 
 #[allow(missing_docs)]
-#[cfg(feature = "channels")]
+#[cfg(any(
+    feature = "channels",
+    feature = "std-channels",
+    feature = "crossbeam-channels"
+))]
 pub mod channel;
+#[cfg(feature = "codec")]
+pub mod codec;
+mod config;
 mod context_wrap;
+mod future_ext;
+mod headers;
+#[cfg(feature = "http")]
+pub mod http;
+pub mod spawn;
 
 #[cfg(not(feature = "opentelemetry-on"))]
 #[allow(missing_docs)]
@@ -21,25 +38,15 @@ mod off;
 pub trait OpenSpanExt {
     /// Get the context of this span.
     fn get_context(&self) -> Context;
+    /// Get the context of this span and add these attributes
+    /// to the link for this boundary crossing.
+    fn get_context_with(&self, attributes: &[(&str, &str)]) -> Context;
     /// Get the context of the current span.
     fn get_current_context() -> Context;
     /// Get the context as message pack bytes for
     /// sending over process boundaries.
     fn get_context_bytes(&self) -> Vec<u8> {
-        #[cfg(feature = "opentelemetry-on")]
-        {
-            use holochain_serialized_bytes::prelude::*;
-            let wc: WireContext = (&self.get_context().0).into();
-            // This shouldn't fail because there should always be a context
-            // to serialize even if it's empty.
-            let sb: SerializedBytes = wc.try_into().expect("Failed to serialize tracing wire");
-            let ub: UnsafeBytes = sb.into();
-            ub.into()
-        }
-        #[cfg(not(feature = "opentelemetry-on"))]
-        {
-            Vec::with_capacity(0)
-        }
+        self.get_context().to_bytes()
     }
     /// Get the current span as message pack bytes.
     fn get_current_bytes() -> Vec<u8>;
@@ -47,28 +54,80 @@ pub trait OpenSpanExt {
     fn set_context(&self, context: Context);
     /// Set the context of the current span.
     fn set_current_context(context: Context);
-    #[allow(unused_variables)]
+    /// Record another context that this span follows from
+    /// as a link without changing the parent.
+    /// The baggage of the context is merged into this span.
+    /// Call this after [OpenSpanExt::set_context] because setting
+    /// the context replaces the links.
+    fn add_link(&self, context: &Context);
+    /// Set the context of this span from multiple contexts.
+    /// The first context becomes the parent and the
+    /// rest are added as links.
+    fn set_contexts<I>(&self, contexts: I)
+    where
+        I: IntoIterator<Item = Context>,
+        Self: Sized,
+    {
+        let mut contexts = contexts.into_iter();
+        if let Some(parent) = contexts.next() {
+            self.set_context(parent);
+        }
+        for context in contexts {
+            self.add_link(&context);
+        }
+    }
     /// Set the context of this span from bytes over the network.
+    /// If the bytes are not a valid context an error
+    /// is logged and a blank context is set.
     fn set_from_bytes(&self, bytes: Vec<u8>) {
-        #[cfg(feature = "opentelemetry-on")]
-        {
-            use holochain_serialized_bytes::prelude::*;
-            use opentelemetry::api;
-            let sb: SerializedBytes = UnsafeBytes::from(bytes).into();
-            let context = match WireContext::try_from(sb) {
-                Ok(w) => api::Context::from(w).into(),
-                Err(e) => {
-                    tracing::error!(
-                        msg = "Failed to deserialize tracing wire context into context"
-                    );
-                    Context::new()
-                }
-            };
-            self.set_context(context);
+        if let Err(e) = self.try_set_from_bytes(bytes) {
+            tracing::error!(
+                msg = "Failed to deserialize tracing wire context into context",
+                error = %e
+            );
+            self.set_context(Context::new());
         }
     }
+    /// Set the context of this span from bytes over the network.
+    /// Returns an error if the bytes are not a valid context.
+    fn try_set_from_bytes(&self, bytes: Vec<u8>) -> Result<(), WireError> {
+        self.set_context(Context::try_from_bytes(bytes)?);
+        Ok(())
+    }
     /// Set the current span context from message pack bytes.
     fn set_current_bytes(bytes: Vec<u8>);
+    /// Write the context of this span into W3C `traceparent`
+    /// and `baggage` headers for sending over process boundaries.
+    fn get_context_headers(&self, headers: &mut dyn HeaderInjector) {
+        self.get_context_headers_with(Propagator::default(), headers)
+    }
+    #[allow(unused_variables)]
+    /// Write the context of this span into headers using
+    /// the format of the chosen [Propagator].
+    fn get_context_headers_with(&self, propagator: Propagator, headers: &mut dyn HeaderInjector) {
+        #[cfg(feature = "opentelemetry-on")]
+        {
+            headers::inject(propagator, &self.get_context().0, headers);
+        }
+    }
+    /// Set the context of this span from W3C `traceparent`
+    /// and `baggage` headers.
+    fn set_from_headers(&self, headers: &dyn HeaderExtractor) {
+        self.set_from_headers_with(Propagator::default(), headers)
+    }
+    #[allow(unused_variables)]
+    /// Set the context of this span from headers using
+    /// the format of the chosen [Propagator].
+    fn set_from_headers_with(&self, propagator: Propagator, headers: &dyn HeaderExtractor) {
+        #[cfg(feature = "opentelemetry-on")]
+        {
+            self.set_context(headers::extract(propagator, headers).into());
+        }
+    }
+    /// Add a key/value to the baggage of this span.
+    /// Baggage is inherited by child spans and is
+    /// propagated along with the context.
+    fn set_baggage(&self, key: &str, value: &str);
     /// Display this spans context as a String.
     fn display_context(&self) -> String;
 }
@@ -80,14 +139,25 @@ mod on {
 
     use super::*;
     use holochain_serialized_bytes::prelude::*;
-    use opentelemetry::api::{self, KeyValue, Link, SpanContext, TraceContextExt, Value};
+    use opentelemetry::api::{
+        self, CorrelationContextExt, KeyValue, Link, SpanContext, TraceContextExt, Value,
+    };
+    use std::sync::atomic::AtomicBool;
     use std::sync::atomic::Ordering;
-    use std::{collections::HashMap, ffi::OsString, sync::atomic::AtomicBool};
-    use tracing::{span::Attributes, Subscriber};
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+    use tracing::{
+        field::{Field, Visit},
+        span::{Attributes, Record},
+        Subscriber,
+    };
     use tracing_opentelemetry::OpenTelemetrySpanExt;
     use tracing_subscriber::{registry::LookupSpan, Layer};
 
     pub(crate) static OPEN_ON: AtomicBool = AtomicBool::new(false);
+    /// Attribute key of the link that replaces elided links.
+    const ELIDED_KEY: &str = "elided";
+    /// Attribute key of links added with [OpenSpanExt::add_link].
+    const FAN_IN_KEY: &str = "fan_in";
     static CONFIG: OnceCell<Config> = OnceCell::new();
     static PROCESS_NAME: OnceCell<String> = OnceCell::new();
 
@@ -97,36 +167,71 @@ mod on {
     #[derive(Debug, Clone, derive_more::From, derive_more::Into)]
     pub struct Context(pub(super) api::Context);
 
-    /// Configuration for open telemetry tracing.
-    /// These can all be configured by setting the
-    /// `OPEN_TEL='process:true,file:false'`.
-    /// They all have default settings.
-    #[derive(Debug, Clone)]
-    pub struct Config {
-        /// Propagate the name of the process running
-        /// on the sender side of the boundary crossing
-        /// in the context and output when calling `spawn_context!()`.
-        /// [Default: false]
-        pub process: bool,
-        /// Propagate the name of the file and line
-        /// number of the sender side of the boundary crossing
-        /// in the context and output when calling `spawn_context!()`.
-        /// [Default: false]
-        pub file: bool,
-        /// Propagate the name of the span name
-        /// of the sender side of the boundary crossing
-        /// in the context and output when calling `spawn_context!()`.
-        /// [Default: true]
-        pub span_name: bool,
-        /// Require there to be a span enabled for the sending side of
-        /// the boundary crossing. [Default: true]
-        pub require_span: bool,
-    }
+    /// The version of the wire format.
+    /// Bump this when the format of [WireContext] changes.
+    pub const WIRE_VERSION: u8 = 1;
 
+    /// A context that can be sent over process boundaries.
+    /// Field names are kept short to keep messages small.
     #[derive(Debug, Clone, Serialize, Deserialize, SerializedBytes)]
     pub struct WireContext {
+        #[serde(rename = "v")]
+        version: u8,
+        #[serde(rename = "c")]
         span_context: WireSpanContext,
+        #[serde(rename = "l", default)]
         links: Option<WireLinks>,
+        #[serde(rename = "b", default)]
+        baggage: Option<Vec<api::KeyValue>>,
+        /// Microseconds since the unix epoch when
+        /// the sender got the context.
+        #[serde(rename = "ts", default)]
+        sent_at: Option<u64>,
+    }
+
+    /// Just the version of a [WireContext] so it can be
+    /// checked before decoding the rest of the context.
+    #[derive(Debug, Deserialize)]
+    struct WireVersion {
+        #[serde(rename = "v")]
+        version: u8,
+    }
+
+    /// The baggage set on a span.
+    /// Stored in the span's extensions.
+    #[derive(Debug, Clone, Default)]
+    struct Baggage(Vec<KeyValue>);
+
+    /// When the sender got the context.
+    #[derive(Debug, Clone, Copy)]
+    struct SentAt(SystemTime);
+
+    /// Size of the message that carried the context.
+    #[derive(Debug, Clone, Copy)]
+    struct MessageSize(usize);
+
+    /// The values of the span fields from [Config::link_fields].
+    /// Stored in the span's extensions.
+    #[derive(Debug, Clone, Default)]
+    struct LinkFields(Vec<KeyValue>);
+
+    impl Visit for LinkFields {
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.record(field, format!("{:?}", value));
+        }
+
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.record(field, value.to_string());
+        }
+    }
+
+    impl LinkFields {
+        fn record(&mut self, field: &Field, value: String) {
+            if Config::link_fields().iter().any(|f| f == field.name()) {
+                self.0.retain(|kv| kv.key.as_str() != field.name());
+                self.0.push(KeyValue::new(field.name(), value));
+            }
+        }
     }
 
     #[derive(
@@ -134,19 +239,27 @@ mod on {
     )]
     pub struct WireLinks(pub Vec<WireLink>);
 
-    /// Needed because SB doesn't do u128
     #[derive(Debug, Clone, Serialize, Deserialize, SerializedBytes)]
     pub struct WireLink {
+        #[serde(rename = "c")]
         span_context: WireSpanContext,
+        #[serde(rename = "a")]
         attributes: Vec<api::KeyValue>,
     }
 
-    /// Needed because SB doesn't do u128
+    /// The ids are sent as fixed size big endian bytes
+    /// because SB doesn't do u128.
     #[derive(Debug, Clone, Serialize, Deserialize, SerializedBytes)]
     pub struct WireSpanContext {
-        trace_id: String,
-        span_id: api::SpanId,
+        /// 16 bytes
+        #[serde(rename = "t", with = "serde_bytes")]
+        trace_id: Vec<u8>,
+        /// 8 bytes
+        #[serde(rename = "s", with = "serde_bytes")]
+        span_id: Vec<u8>,
+        #[serde(rename = "f")]
         trace_flags: u8,
+        #[serde(rename = "r")]
         is_remote: bool,
     }
 
@@ -157,6 +270,50 @@ mod on {
         pub fn new() -> Self {
             Context(api::Context::new())
         }
+
+        /// Serialize this context to message pack bytes
+        /// for sending over process boundaries.
+        pub fn to_bytes(&self) -> Vec<u8> {
+            context_to_bytes(self)
+        }
+
+        /// Deserialize a context from message pack bytes.
+        pub fn try_from_bytes(bytes: Vec<u8>) -> Result<Self, WireError> {
+            context_from_bytes(bytes)
+        }
+
+        /// Record the size of the message that carried this
+        /// context when the context is set on a span.
+        pub fn with_message_size(&self, bytes: usize) -> Self {
+            Context(self.0.with_value(MessageSize(bytes)))
+        }
+
+        /// Add a key/value to the baggage of this context.
+        pub fn with_baggage(&self, key: &str, value: &str) -> Self {
+            Context(
+                self.0
+                    .with_correlations(Some(KeyValue::new(key.to_string(), value.to_string()))),
+            )
+        }
+
+        /// Get a baggage value from this context.
+        pub fn get_baggage(&self, key: &str) -> Option<&str> {
+            match self.0.correlation_context().get(key.to_string()) {
+                Some(Value::String(v)) => Some(v.as_str()),
+                _ => None,
+            }
+        }
+
+        /// Iterate over all the baggage in this context.
+        pub fn baggage(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+            self.0
+                .correlation_context()
+                .iter()
+                .filter_map(|(k, v)| match v {
+                    Value::String(v) => Some((k.as_str(), v.as_str())),
+                    _ => None,
+                })
+        }
     }
 
     impl Default for Context {
@@ -172,13 +329,18 @@ mod on {
         }
 
         fn get_context(&self) -> Context {
+            self.get_context_with(&[])
+        }
+
+        fn get_context_with(&self, attributes: &[(&str, &str)]) -> Context {
             if should_not_run(self) {
                 return Context::new();
             }
             let context = self.context();
             let span = context.span().span_context();
             let context = context.with_remote_span_context(span);
-            get_followers(self, context).into()
+            let context = get_baggage(self, context).with_value(SentAt(SystemTime::now()));
+            get_followers(self, context, attributes).into()
         }
 
         fn get_current_bytes() -> Vec<u8> {
@@ -193,6 +355,8 @@ mod on {
 
             self.set_parent(&context.0);
             set_followers(self, &context.0);
+            set_baggage(self, &context.0);
+            record_crossing(self, &context.0);
         }
 
         fn set_current_context(context: Context) {
@@ -200,6 +364,15 @@ mod on {
             span.set_context(context);
         }
 
+        fn add_link(&self, context: &Context) {
+            if should_not_run(self) {
+                return;
+            }
+            add_fan_in(self, &context.0);
+            set_baggage(self, &context.0);
+            record_crossing(self, &context.0);
+        }
+
         fn set_current_bytes(bytes: Vec<u8>) {
             let span = tracing::Span::current();
             span.set_from_bytes(bytes)
@@ -212,6 +385,15 @@ mod on {
             let context = self.get_context();
             format!("{}", context)
         }
+
+        fn set_baggage(&self, key: &str, value: &str) {
+            if should_not_run(self) {
+                return;
+            }
+            let context = api::Context::new()
+                .with_correlations(Some(KeyValue::new(key.to_string(), value.to_string())));
+            set_baggage(self, &context);
+        }
     }
 
     /// Emit a tracing event with the context of a span.
@@ -271,29 +453,73 @@ mod on {
             )?;
             if let Some((_, links)) = context.get::<Vec<Link>>().and_then(|l| l.split_last()) {
                 for link in links {
-                    write!(f, " ->")?;
+                    if link
+                        .attributes()
+                        .iter()
+                        .any(|kv| kv.key.as_str() == FAN_IN_KEY)
+                    {
+                        write!(f, " +")?;
+                    } else {
+                        write!(f, " ->")?;
+                    }
                     for kv in link.attributes() {
-                        if let Value::String(v) = &kv.value {
-                            write!(f, " {}: {};", kv.key.as_str(), v)?;
+                        if kv.key.as_str() != FAN_IN_KEY {
+                            write!(f, " {}: {};", kv.key.as_str(), String::from(&kv.value))?;
                         }
                     }
                 }
             }
+            let baggage = context.correlation_context();
+            if !baggage.is_empty() {
+                write!(f, " | baggage:")?;
+                let mut baggage = baggage.iter().collect::<Vec<_>>();
+                baggage.sort_by(|a, b| a.0.cmp(b.0));
+                for (k, v) in baggage {
+                    write!(f, " {}: {};", k.as_str(), String::from(v))?;
+                }
+            }
             Ok(())
         }
     }
 
-    pub(crate) fn init() {
-        CONFIG.get_or_init(|| Config::from(std::env::var_os("OPEN_TEL")));
+    pub(crate) fn set_config(config: Config) -> Result<(), TracingError> {
+        CONFIG
+            .set(config)
+            .map_err(|_| TracingError::ConfigAlreadySet)
+    }
+
+    pub(crate) fn init() -> Result<(), TracingError> {
+        if CONFIG.get().is_none() {
+            // Another thread may have set it in the mean time.
+            CONFIG.set(Config::from_env()?).ok();
+        }
+        process_name();
+        Ok(())
+    }
+
+    fn process_name() -> &'static str {
         PROCESS_NAME.get_or_init(|| {
             std::env::current_exe()
                 .ok()
                 .and_then(|p| p.file_name().map(|s| s.to_string_lossy().into_owned()))
                 .unwrap_or_else(|| "not_found".to_string())
-        });
+        })
+    }
+
+    /// Attributes describing this process.
+    /// Shared by the tracer and the metrics exporter.
+    pub(crate) fn resource() -> Vec<KeyValue> {
+        vec![
+            KeyValue::new("service.name", process_name()),
+            KeyValue::new("process.pid", std::process::id() as i64),
+        ]
     }
 
-    fn get_followers(span: &tracing::Span, context: api::Context) -> api::Context {
+    fn get_followers(
+        span: &tracing::Span,
+        context: api::Context,
+        attributes: &[(&str, &str)],
+    ) -> api::Context {
         let mut links = None;
         span.with_subscriber(|(id, dispatch)| {
             if let Some(registry) = dispatch.downcast_ref::<tracing_subscriber::Registry>() {
@@ -308,12 +534,12 @@ mod on {
 
         let links = links
             .map(|mut l| {
-                if let Some(link) = create_link(span, &context) {
+                if let Some(link) = create_link(span, &context, attributes) {
                     l.push(link);
                 }
-                l
+                truncate_links(l, Config::links_head(), Config::links_tail())
             })
-            .or_else(|| create_link(span, &context).map(|l| vec![l]));
+            .or_else(|| create_link(span, &context, attributes).map(|l| vec![l]));
 
         match links {
             Some(links) => context.with_value(links),
@@ -321,6 +547,85 @@ mod on {
         }
     }
 
+    /// Record how long the context took to cross the
+    /// boundary and how big the message was.
+    fn record_crossing(span: &tracing::Span, context: &api::Context) {
+        use crate::metrics::Boundary;
+        // Clocks may differ between processes so ignore negative latency.
+        let latency = context
+            .get::<SentAt>()
+            .and_then(|s| SystemTime::now().duration_since(s.0).ok())
+            .map(|d| d.as_micros() as u64);
+        let size = context.get::<MessageSize>().map(|s| s.0);
+        if latency.is_none() && size.is_none() {
+            return;
+        }
+        Boundary::count_silent(Boundary::Crossings, 1);
+        if let Some(latency) = latency {
+            Boundary::count_silent(Boundary::QueueLatencyMicros, latency);
+        }
+        if let Some(size) = size {
+            Boundary::count_silent(Boundary::MessageBytes, size);
+        }
+        tracing::trace!(parent: span, queue_latency_us = ?latency, message_bytes = ?size);
+    }
+
+    fn add_fan_in(span: &tracing::Span, context: &api::Context) {
+        let span_context = match context.remote_span_context() {
+            Some(span_context) if span_context.is_valid() => span_context.clone(),
+            _ => return,
+        };
+        // The last link was created by the sender so use its attributes.
+        let mut attributes = context
+            .get::<Vec<Link>>()
+            .and_then(|links| links.last())
+            .map(|link| link.attributes().clone())
+            .unwrap_or_default();
+        attributes.push(KeyValue::new(FAN_IN_KEY, true));
+        let link = Link::new(span_context, attributes);
+        span.with_subscriber(|(id, dispatch)| {
+            if let Some(registry) = dispatch.downcast_ref::<tracing_subscriber::Registry>() {
+                if let Some(span_ref) = registry.span(id) {
+                    let mut extensions = span_ref.extensions_mut();
+                    if let Some(sb) = extensions.get_mut::<api::SpanBuilder>() {
+                        sb.links.get_or_insert_with(Vec::new).push(link);
+                    }
+                }
+            }
+        });
+    }
+
+    /// Keep the first `head` and last `tail` links and replace
+    /// the links in between with a single link that records
+    /// how many links were elided.
+    fn truncate_links(links: Vec<Link>, head: usize, tail: usize) -> Vec<Link> {
+        if links.len() <= head + tail {
+            return links;
+        }
+        let end = links.len() - tail;
+        let elided: u64 = links[head..end].iter().map(elided_count).sum();
+        let mut truncated = Vec::with_capacity(head + tail + 1);
+        truncated.extend_from_slice(&links[..head]);
+        truncated.push(Link::new(
+            SpanContext::empty_context(),
+            vec![KeyValue::new(ELIDED_KEY, elided.to_string())],
+        ));
+        truncated.extend_from_slice(&links[end..]);
+        truncated
+    }
+
+    /// How many links this link stands for.
+    fn elided_count(link: &Link) -> u64 {
+        link.attributes()
+            .iter()
+            .find(|kv| kv.key.as_str() == ELIDED_KEY)
+            .and_then(|kv| match &kv.value {
+                Value::String(v) => v.parse().ok(),
+                _ => None,
+            })
+            .unwrap_or(1)
+    }
+
     fn set_followers(span: &tracing::Span, context: &api::Context) {
         let new_links = context.get::<Vec<Link>>().cloned().unwrap_or_default();
         if !new_links.is_empty() {
@@ -345,11 +650,66 @@ mod on {
         }
     }
 
-    fn create_link(span: &tracing::Span, context: &api::Context) -> Option<Link> {
+    fn get_baggage(span: &tracing::Span, context: api::Context) -> api::Context {
+        let mut baggage = None;
+        span.with_subscriber(|(id, dispatch)| {
+            if let Some(registry) = dispatch.downcast_ref::<tracing_subscriber::Registry>() {
+                if let Some(span_ref) = registry.span(id) {
+                    baggage = span_ref.extensions().get::<Baggage>().cloned();
+                }
+            }
+        });
+        match baggage {
+            Some(baggage) => context.with_correlations(baggage.0),
+            None => context,
+        }
+    }
+
+    fn set_baggage(span: &tracing::Span, context: &api::Context) {
+        let new_baggage = context.correlation_context();
+        if !new_baggage.is_empty() {
+            span.with_subscriber(|(id, dispatch)| {
+                if let Some(registry) = dispatch.downcast_ref::<tracing_subscriber::Registry>() {
+                    if let Some(span_ref) = registry.span(id) {
+                        let mut extensions = span_ref.extensions_mut();
+                        let mut baggage = extensions.remove::<Baggage>().unwrap_or_default();
+                        for (k, v) in new_baggage {
+                            baggage.0.retain(|kv| &kv.key != k);
+                            baggage.0.push(KeyValue::new(k.clone(), v.clone()));
+                        }
+                        extensions.insert(baggage);
+                    }
+                }
+            });
+        }
+    }
+
+    /// The name of the span including any `otel.name` override.
+    fn span_name(span: &tracing::Span, default: &'static str) -> String {
+        let mut name = default.to_string();
+        span.with_subscriber(|(id, dispatch)| {
+            if let Some(registry) = dispatch.downcast_ref::<tracing_subscriber::Registry>() {
+                if let Some(otel_name) = registry.span(id).and_then(|s| {
+                    s.extensions()
+                        .get::<api::SpanBuilder>()
+                        .map(|b| b.name.to_string())
+                }) {
+                    name = otel_name;
+                }
+            }
+        });
+        name
+    }
+
+    fn create_link(
+        span: &tracing::Span,
+        context: &api::Context,
+        attributes: &[(&str, &str)],
+    ) -> Option<Link> {
         if let Some(meta) = span.metadata() {
             let mut kvs = Vec::with_capacity(2);
             if Config::span_name() {
-                kvs.push(KeyValue::new("span", meta.name()));
+                kvs.push(KeyValue::new("span", span_name(span, meta.name())));
             }
             if Config::file() {
                 if let (Some(file), Some(line)) = (meta.file(), meta.line()) {
@@ -365,6 +725,20 @@ mod on {
                         .unwrap_or_else(|| "not_found".to_string()),
                 ))
             }
+            span.with_subscriber(|(id, dispatch)| {
+                if let Some(registry) = dispatch.downcast_ref::<tracing_subscriber::Registry>() {
+                    if let Some(span_ref) = registry.span(id) {
+                        if let Some(fields) = span_ref.extensions().get::<LinkFields>() {
+                            kvs.extend(fields.0.iter().cloned());
+                        }
+                    }
+                }
+            });
+            kvs.extend(
+                attributes
+                    .iter()
+                    .map(|(k, v)| KeyValue::new(k.to_string(), v.to_string())),
+            );
             let span_context = context.span().span_context();
             return Some(Link::new(span_context, kvs));
         }
@@ -396,6 +770,27 @@ mod on {
                 .map(|c| c.process)
                 .unwrap_or_else(|| Config::default().process)
         }
+        fn links_head() -> usize {
+            CONFIG
+                .get()
+                .map(|c| c.links_head)
+                .unwrap_or_else(|| Config::default().links_head)
+        }
+        fn links_tail() -> usize {
+            CONFIG
+                .get()
+                .map(|c| c.links_tail)
+                .unwrap_or_else(|| Config::default().links_tail)
+        }
+        fn max_link_bytes() -> usize {
+            CONFIG
+                .get()
+                .map(|c| c.max_link_bytes)
+                .unwrap_or_else(|| Config::default().max_link_bytes)
+        }
+        fn link_fields() -> &'static [String] {
+            CONFIG.get().map(|c| &c.link_fields[..]).unwrap_or(&[])
+        }
     }
 
     pub struct OpenLayer;
@@ -412,6 +807,13 @@ mod on {
         ) {
             let span = ctx.span(id).expect("Span should not be missing");
             let mut extensions = span.extensions_mut();
+            if !Config::link_fields().is_empty() {
+                let mut fields = LinkFields::default();
+                attrs.record(&mut fields);
+                if !fields.0.is_empty() {
+                    extensions.insert(fields);
+                }
+            }
             if let Some(parent) = attrs.parent() {
                 let parent = ctx.span(parent).expect("Span should not be missing");
                 let parent_extensions = parent.extensions();
@@ -421,6 +823,9 @@ mod on {
                 {
                     s.links = p.links.clone()
                 }
+                if let Some(baggage) = parent_extensions.get::<Baggage>() {
+                    extensions.insert(baggage.clone());
+                }
             } else if attrs.is_contextual() {
                 if let Some(parent) = ctx.lookup_current() {
                     let parent_extensions = parent.extensions();
@@ -430,9 +835,78 @@ mod on {
                     {
                         s.links = p.links.clone()
                     }
+                    if let Some(baggage) = parent_extensions.get::<Baggage>() {
+                        extensions.insert(baggage.clone());
+                    }
                 }
             }
         }
+
+        fn on_record(
+            &self,
+            id: &tracing::span::Id,
+            values: &Record<'_>,
+            ctx: tracing_subscriber::layer::Context<'_, S>,
+        ) {
+            if Config::link_fields().is_empty() {
+                return;
+            }
+            let span = ctx.span(id).expect("Span should not be missing");
+            let mut extensions = span.extensions_mut();
+            let mut fields = extensions.remove::<LinkFields>().unwrap_or_default();
+            values.record(&mut fields);
+            if !fields.0.is_empty() {
+                extensions.insert(fields);
+            }
+        }
+    }
+
+    pub(crate) fn context_to_bytes(context: &Context) -> Vec<u8> {
+        let links = context.0.get::<Vec<Link>>();
+        let max_bytes = Config::max_link_bytes();
+        let (mut head, mut tail) = (Config::links_head(), Config::links_tail());
+        loop {
+            let bytes = match links {
+                Some(links) => encode_context(&context.0.with_value(truncate_links(
+                    links.clone(),
+                    head,
+                    tail,
+                ))),
+                None => encode_context(&context.0),
+            };
+            if max_bytes == 0 || bytes.len() <= max_bytes || head + tail == 0 {
+                return bytes;
+            }
+            // Elide more links from the middle of the chain.
+            if tail > 0 {
+                tail -= 1;
+            } else {
+                head -= 1;
+            }
+        }
+    }
+
+    fn encode_context(context: &api::Context) -> Vec<u8> {
+        let wc: WireContext = context.into();
+        // This shouldn't fail because there should always be a context
+        // to serialize even if it's empty.
+        let sb: SerializedBytes = wc.try_into().expect("Failed to serialize tracing wire");
+        let ub: UnsafeBytes = sb.into();
+        ub.into()
+    }
+
+    pub(crate) fn context_from_bytes(bytes: Vec<u8>) -> Result<Context, WireError> {
+        let WireVersion { version } = holochain_serialized_bytes::decode(&bytes)
+            .map_err(|e| WireError::Deserialize(e.to_string()))?;
+        if version != WIRE_VERSION {
+            return Err(WireError::UnsupportedVersion(version));
+        }
+        let sb: SerializedBytes = UnsafeBytes::from(bytes).into();
+        let len = sb.bytes().len();
+        let wc = WireContext::try_from(sb).map_err(|e| WireError::Deserialize(e.to_string()))?;
+        Ok(api::Context::try_from(wc)?
+            .with_value(MessageSize(len))
+            .into())
     }
 
     impl From<&api::Context> for WireContext {
@@ -442,21 +916,53 @@ mod on {
                 .get::<Vec<Link>>()
                 .cloned()
                 .map(|links| WireLinks(links.into_iter().map(WireLink::from).collect()));
+            let baggage = c.correlation_context();
+            let baggage = if baggage.is_empty() {
+                None
+            } else {
+                Some(
+                    baggage
+                        .iter()
+                        .map(|(k, v)| KeyValue::new(k.clone(), v.clone()))
+                        .collect(),
+                )
+            };
+            let sent_at = c
+                .get::<SentAt>()
+                .and_then(|s| s.0.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_micros() as u64);
             WireContext {
+                version: WIRE_VERSION,
                 span_context,
                 links,
+                baggage,
+                sent_at,
             }
         }
     }
 
-    impl From<WireContext> for api::Context {
-        fn from(wc: WireContext) -> Self {
-            let mut c = api::Context::new().with_remote_span_context(wc.span_context.into());
+    impl TryFrom<WireContext> for api::Context {
+        type Error = WireError;
+        fn try_from(wc: WireContext) -> Result<Self, Self::Error> {
+            if wc.version != WIRE_VERSION {
+                return Err(WireError::UnsupportedVersion(wc.version));
+            }
+            let mut c = api::Context::new().with_remote_span_context(wc.span_context.try_into()?);
             if let Some(links) = wc.links {
-                let links: Vec<Link> = links.0.into_iter().map(Link::from).collect();
+                let links = links
+                    .0
+                    .into_iter()
+                    .map(Link::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
                 c = c.with_value(links);
             }
-            c
+            if let Some(baggage) = wc.baggage {
+                c = c.with_correlations(baggage);
+            }
+            if let Some(sent_at) = wc.sent_at {
+                c = c.with_value(SentAt(UNIX_EPOCH + Duration::from_micros(sent_at)));
+            }
+            Ok(c)
         }
     }
 
@@ -469,86 +975,37 @@ mod on {
         }
     }
 
-    impl From<WireLink> for Link {
-        fn from(wl: WireLink) -> Self {
-            Link::new(wl.span_context.into(), wl.attributes)
+    impl TryFrom<WireLink> for Link {
+        type Error = WireError;
+        fn try_from(wl: WireLink) -> Result<Self, Self::Error> {
+            Ok(Link::new(wl.span_context.try_into()?, wl.attributes))
         }
     }
 
     impl From<SpanContext> for WireSpanContext {
         fn from(sc: SpanContext) -> Self {
             WireSpanContext {
-                trace_id: sc.trace_id().to_u128().to_string(),
-                span_id: sc.span_id(),
+                trace_id: sc.trace_id().to_u128().to_be_bytes().to_vec(),
+                span_id: sc.span_id().to_u64().to_be_bytes().to_vec(),
                 trace_flags: sc.trace_flags(),
                 is_remote: sc.is_remote(),
             }
         }
     }
 
-    impl From<WireSpanContext> for SpanContext {
-        fn from(wsc: WireSpanContext) -> Self {
-            SpanContext::new(
-                api::TraceId::from_u128(
-                    wsc.trace_id
-                        .parse::<u128>()
-                        .expect("Failed to parse trace id"),
-                ),
-                wsc.span_id,
+    impl TryFrom<WireSpanContext> for SpanContext {
+        type Error = WireError;
+        fn try_from(wsc: WireSpanContext) -> Result<Self, Self::Error> {
+            let trace_id = <[u8; 16]>::try_from(&wsc.trace_id[..])
+                .map_err(|_| WireError::TraceIdLength(wsc.trace_id.len()))?;
+            let span_id = <[u8; 8]>::try_from(&wsc.span_id[..])
+                .map_err(|_| WireError::SpanIdLength(wsc.span_id.len()))?;
+            Ok(SpanContext::new(
+                api::TraceId::from_u128(u128::from_be_bytes(trace_id)),
+                api::SpanId::from_u64(u64::from_be_bytes(span_id)),
                 wsc.trace_flags,
                 wsc.is_remote,
-            )
-        }
-    }
-
-    impl Default for Config {
-        fn default() -> Self {
-            Self {
-                process: false,
-                file: false,
-                span_name: true,
-                require_span: true,
-            }
-        }
-    }
-
-    impl From<Option<OsString>> for Config {
-        fn from(var: Option<OsString>) -> Self {
-            let var = match var.and_then(|v| v.into_string().ok()) {
-                Some(var) => var,
-                None => return Self::default(),
-            };
-            let options = var.split(',').filter_map(|kv|{
-                let kv = kv.split(':').map(|i|i.trim()).collect::<Vec<_>>();
-                if kv.len() == 2 {
-                    Some((kv[0], kv[1]))
-                } else {
-                    eprintln!("Failed to parse config from OPEN_TEL.\nFormat is `OPEN_TEL='key: value, key: value'`");
-                    None
-                }
-            })
-            .collect::<HashMap<&str, &str>>();
-            let mut config = Config::default();
-            if let Some(file) = options.get("file").and_then(|v| v.parse::<bool>().ok()) {
-                config.file = file;
-            }
-            if let Some(process) = options.get("process").and_then(|v| v.parse::<bool>().ok()) {
-                config.process = process;
-            }
-            if let Some(span_name) = options
-                .get("span_name")
-                .and_then(|v| v.parse::<bool>().ok())
-            {
-                config.span_name = span_name;
-            }
-            if let Some(require_span) = options
-                .get("require_span")
-                .and_then(|v| v.parse::<bool>().ok())
-            {
-                config.require_span = require_span;
-            }
-
-            config
+            ))
         }
     }
 }
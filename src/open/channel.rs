@@ -1,16 +1,27 @@
 use crate::MsgWrap;
-use derive_more::{From, Into};
-use shrinkwraprs::Shrinkwrap;
+use derive_more::From;
+#[cfg(feature = "channels")]
+use futures_core::Stream;
+#[cfg(feature = "channels")]
+use std::pin::Pin;
+#[cfg(feature = "channels")]
+use std::task::{Context, Poll};
+use std::time::Duration;
 
+#[cfg(feature = "crossbeam-channels")]
+pub mod crossbeam;
+#[cfg(feature = "std-channels")]
+pub mod std_mpsc;
+
+#[cfg(feature = "channels")]
 pub mod mpsc {
     use super::*;
+    use tokio::sync::mpsc::error::{SendTimeoutError, TryRecvError, TrySendError};
 
-    #[derive(From, Into, Shrinkwrap)]
-    #[shrinkwrap(mutable)]
-    pub struct Sender<T>(pub tokio::sync::mpsc::Sender<MsgWrap<T>>);
-    #[derive(From, Into, Shrinkwrap)]
-    #[shrinkwrap(mutable)]
-    pub struct Receiver<T>(pub tokio::sync::mpsc::Receiver<MsgWrap<T>>);
+    #[derive(From)]
+    pub struct Sender<T>(tokio::sync::mpsc::Sender<MsgWrap<T>>);
+    #[derive(From)]
+    pub struct Receiver<T>(tokio::sync::mpsc::Receiver<MsgWrap<T>>);
 
     pub fn channel<T>(buffer: usize) -> (Sender<T>, Receiver<T>) {
         let (tx, rx) = tokio::sync::mpsc::channel(buffer);
@@ -33,24 +44,232 @@ pub mod mpsc {
                 .await
                 .map_err(|e| tokio::sync::mpsc::error::SendError(e.0.without_context()))
         }
+
+        pub fn try_send(&mut self, value: T) -> Result<(), TrySendError<T>> {
+            self.0.try_send(value.into()).map_err(|e| match e {
+                TrySendError::Full(t) => TrySendError::Full(t.without_context()),
+                TrySendError::Closed(t) => TrySendError::Closed(t.without_context()),
+            })
+        }
+
+        pub async fn send_timeout(
+            &mut self,
+            value: T,
+            timeout: Duration,
+        ) -> Result<(), SendTimeoutError<T>> {
+            self.0
+                .send_timeout(value.into(), timeout)
+                .await
+                .map_err(|e| match e {
+                    SendTimeoutError::Timeout(t) => SendTimeoutError::Timeout(t.without_context()),
+                    SendTimeoutError::Closed(t) => SendTimeoutError::Closed(t.without_context()),
+                })
+        }
+
+        /// Reserve a slot for a `try_send`.
+        /// Returns an error if the receiver is closed.
+        pub fn poll_ready(
+            &mut self,
+            cx: &mut Context<'_>,
+        ) -> Poll<Result<(), tokio::sync::mpsc::error::ClosedError>> {
+            self.0.poll_ready(cx)
+        }
+
+        /// Release a slot reserved by `poll_ready`.
+        pub fn disarm(&mut self) -> bool {
+            self.0.disarm()
+        }
     }
 
     impl<T> Receiver<T> {
         pub async fn recv(&mut self) -> Option<T> {
             self.0.recv().await.map(|t| t.inner())
         }
+
+        pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            self.0.poll_recv(cx).map(|t| t.map(MsgWrap::inner))
+        }
+
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            self.0.try_recv().map(MsgWrap::inner)
+        }
+
+        pub fn close(&mut self) {
+            self.0.close()
+        }
+    }
+
+    impl<T> Stream for Receiver<T> {
+        type Item = T;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            self.poll_recv(cx)
+        }
+    }
+
+    #[derive(From)]
+    pub struct UnboundedSender<T>(tokio::sync::mpsc::UnboundedSender<MsgWrap<T>>);
+    #[derive(From)]
+    pub struct UnboundedReceiver<T>(tokio::sync::mpsc::UnboundedReceiver<MsgWrap<T>>);
+
+    pub fn unbounded_channel<T>() -> (UnboundedSender<T>, UnboundedReceiver<T>) {
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+        (tx.into(), rx.into())
+    }
+
+    impl<T> Clone for UnboundedSender<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T> UnboundedSender<T> {
+        pub fn send(&self, value: T) -> Result<(), tokio::sync::mpsc::error::SendError<T>> {
+            self.0
+                .send(value.into())
+                .map_err(|e| tokio::sync::mpsc::error::SendError(e.0.without_context()))
+        }
+    }
+
+    impl<T> UnboundedReceiver<T> {
+        pub async fn recv(&mut self) -> Option<T> {
+            self.0.recv().await.map(|t| t.inner())
+        }
+
+        pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            self.0.poll_recv(cx).map(|t| t.map(MsgWrap::inner))
+        }
+
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            self.0.try_recv().map(MsgWrap::inner)
+        }
+
+        pub fn close(&mut self) {
+            self.0.close()
+        }
+    }
+
+    impl<T> Stream for UnboundedReceiver<T> {
+        type Item = T;
+
+        fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            self.poll_recv(cx)
+        }
     }
 }
 
+#[cfg(feature = "channels")]
+pub mod broadcast {
+    use super::*;
+
+    #[derive(From)]
+    pub struct Sender<T>(tokio::sync::broadcast::Sender<MsgWrap<T>>);
+    #[derive(From)]
+    pub struct Receiver<T>(tokio::sync::broadcast::Receiver<MsgWrap<T>>);
+
+    pub fn channel<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        let (tx, rx) = tokio::sync::broadcast::channel(capacity);
+        (tx.into(), rx.into())
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T> Sender<T> {
+        /// Every receiver gets the context of the current span.
+        pub fn send(&self, value: T) -> Result<usize, tokio::sync::broadcast::SendError<T>> {
+            self.0
+                .send(value.into())
+                .map_err(|e| tokio::sync::broadcast::SendError(e.0.without_context()))
+        }
+
+        pub fn subscribe(&self) -> Receiver<T> {
+            self.0.subscribe().into()
+        }
+
+        pub fn receiver_count(&self) -> usize {
+            self.0.receiver_count()
+        }
+    }
+
+    impl<T: Clone> Receiver<T> {
+        pub async fn recv(&mut self) -> Result<T, tokio::sync::broadcast::RecvError> {
+            self.0.recv().await.map(MsgWrap::inner)
+        }
+
+        pub fn try_recv(&mut self) -> Result<T, tokio::sync::broadcast::TryRecvError> {
+            self.0.try_recv().map(MsgWrap::inner)
+        }
+    }
+}
+
+#[cfg(feature = "channels")]
+pub mod watch {
+    use super::*;
+
+    #[derive(From)]
+    pub struct Sender<T>(tokio::sync::watch::Sender<MsgWrap<T>>);
+    #[derive(From)]
+    pub struct Receiver<T>(tokio::sync::watch::Receiver<MsgWrap<T>>);
+
+    /// The initial value has the context of the current span.
+    pub fn channel<T: Clone>(init: T) -> (Sender<T>, Receiver<T>) {
+        let (tx, rx) = tokio::sync::watch::channel(init.into());
+        (tx.into(), rx.into())
+    }
+
+    impl<T> Clone for Receiver<T> {
+        fn clone(&self) -> Self {
+            Self(self.0.clone())
+        }
+    }
+
+    impl<T: Clone> Sender<T> {
+        /// Returns the value if every receiver has been dropped.
+        /// A clone is kept to give back because the value
+        /// can't be taken out of tokio's watch error.
+        pub fn broadcast(&self, value: T) -> Result<(), T> {
+            let msg: MsgWrap<T> = value.into();
+            self.0
+                .broadcast(msg.clone())
+                .map_err(|_| msg.without_context())
+        }
+
+        /// Completes when every receiver has been dropped.
+        pub async fn closed(&mut self) {
+            self.0.closed().await
+        }
+    }
+
+    impl<T: Clone> Receiver<T> {
+        pub async fn recv(&mut self) -> Option<T> {
+            self.0.recv().await.map(MsgWrap::inner)
+        }
+
+        /// Get the value if it has changed since
+        /// it was last received without waiting.
+        pub fn try_recv(&mut self) -> Option<T> {
+            let waker = futures_task::noop_waker();
+            let mut cx = Context::from_waker(&waker);
+            match self.0.poll_recv_ref(&mut cx) {
+                Poll::Ready(Some(r)) => Some(r.clone().inner()),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "channels")]
 pub mod oneshot {
     use super::*;
 
-    #[derive(From, Into, Shrinkwrap)]
-    #[shrinkwrap(mutable)]
-    pub struct Sender<T>(pub tokio::sync::oneshot::Sender<MsgWrap<T>>);
-    #[derive(From, Into, Shrinkwrap)]
-    #[shrinkwrap(mutable)]
-    pub struct Receiver<T>(pub tokio::sync::oneshot::Receiver<MsgWrap<T>>);
+    #[derive(From)]
+    pub struct Sender<T>(tokio::sync::oneshot::Sender<MsgWrap<T>>);
+    #[derive(From)]
+    pub struct Receiver<T>(tokio::sync::oneshot::Receiver<MsgWrap<T>>);
 
     pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
         let (tx, rx) = tokio::sync::oneshot::channel();
@@ -61,17 +280,35 @@ pub mod oneshot {
         pub fn send(self, value: T) -> Result<(), T> {
             self.0.send(value.into()).map_err(|e| e.without_context())
         }
+
+        pub fn is_closed(&self) -> bool {
+            self.0.is_closed()
+        }
+
+        pub async fn closed(&mut self) {
+            self.0.closed().await
+        }
+
+        pub fn poll_closed(&mut self, cx: &mut Context<'_>) -> Poll<()> {
+            self.0.poll_closed(cx)
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn try_recv(&mut self) -> Result<T, tokio::sync::oneshot::error::TryRecvError> {
+            self.0.try_recv().map(MsgWrap::inner)
+        }
+
+        pub fn close(&mut self) {
+            self.0.close()
+        }
     }
 
     impl<T> std::future::Future for Receiver<T> {
         type Output = Result<T, tokio::sync::oneshot::error::RecvError>;
 
-        fn poll(
-            mut self: std::pin::Pin<&mut Self>,
-            cx: &mut std::task::Context<'_>,
-        ) -> std::task::Poll<Self::Output> {
-            use std::task::Poll;
-            let p = std::pin::Pin::new(&mut self.0);
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let p = Pin::new(&mut self.0);
             match tokio::sync::oneshot::Receiver::poll(p, cx) {
                 Poll::Ready(r) => Poll::Ready(r.map(MsgWrap::inner)),
                 Poll::Pending => Poll::Pending,
@@ -0,0 +1,97 @@
+use super::*;
+use crossbeam_channel::{
+    RecvError, RecvTimeoutError, SendError, SendTimeoutError, TryRecvError, TrySendError,
+};
+
+#[derive(From)]
+pub struct Sender<T>(crossbeam_channel::Sender<MsgWrap<T>>);
+#[derive(From)]
+pub struct Receiver<T>(crossbeam_channel::Receiver<MsgWrap<T>>);
+
+pub fn bounded<T>(cap: usize) -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = crossbeam_channel::bounded(cap);
+    (tx.into(), rx.into())
+}
+
+pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = crossbeam_channel::unbounded();
+    (tx.into(), rx.into())
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Clone for Receiver<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Sender<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.0
+            .send(value.into())
+            .map_err(|e| SendError(e.0.without_context()))
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.0.try_send(value.into()).map_err(|e| match e {
+            TrySendError::Full(t) => TrySendError::Full(t.without_context()),
+            TrySendError::Disconnected(t) => TrySendError::Disconnected(t.without_context()),
+        })
+    }
+
+    pub fn send_timeout(&self, value: T, timeout: Duration) -> Result<(), SendTimeoutError<T>> {
+        self.0
+            .send_timeout(value.into(), timeout)
+            .map_err(|e| match e {
+                SendTimeoutError::Timeout(t) => SendTimeoutError::Timeout(t.without_context()),
+                SendTimeoutError::Disconnected(t) => {
+                    SendTimeoutError::Disconnected(t.without_context())
+                }
+            })
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.0.recv().map(MsgWrap::inner)
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.0.try_recv().map(MsgWrap::inner)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.0.recv_timeout(timeout).map(MsgWrap::inner)
+    }
+
+    /// Each message's context is set on the
+    /// current span as it is yielded.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.0.iter().map(MsgWrap::inner)
+    }
+
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.0.try_iter().map(MsgWrap::inner)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
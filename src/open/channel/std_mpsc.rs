@@ -0,0 +1,78 @@
+use super::*;
+use std::sync::mpsc::{RecvError, RecvTimeoutError, SendError, TryRecvError, TrySendError};
+
+#[derive(From)]
+pub struct Sender<T>(std::sync::mpsc::Sender<MsgWrap<T>>);
+#[derive(From)]
+pub struct SyncSender<T>(std::sync::mpsc::SyncSender<MsgWrap<T>>);
+#[derive(From)]
+pub struct Receiver<T>(std::sync::mpsc::Receiver<MsgWrap<T>>);
+
+pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+    let (tx, rx) = std::sync::mpsc::channel();
+    (tx.into(), rx.into())
+}
+
+pub fn sync_channel<T>(bound: usize) -> (SyncSender<T>, Receiver<T>) {
+    let (tx, rx) = std::sync::mpsc::sync_channel(bound);
+    (tx.into(), rx.into())
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Clone for SyncSender<T> {
+    fn clone(&self) -> Self {
+        Self(self.0.clone())
+    }
+}
+
+impl<T> Sender<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.0
+            .send(value.into())
+            .map_err(|e| SendError(e.0.without_context()))
+    }
+}
+
+impl<T> SyncSender<T> {
+    pub fn send(&self, value: T) -> Result<(), SendError<T>> {
+        self.0
+            .send(value.into())
+            .map_err(|e| SendError(e.0.without_context()))
+    }
+
+    pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+        self.0.try_send(value.into()).map_err(|e| match e {
+            TrySendError::Full(t) => TrySendError::Full(t.without_context()),
+            TrySendError::Disconnected(t) => TrySendError::Disconnected(t.without_context()),
+        })
+    }
+}
+
+impl<T> Receiver<T> {
+    pub fn recv(&self) -> Result<T, RecvError> {
+        self.0.recv().map(MsgWrap::inner)
+    }
+
+    pub fn try_recv(&self) -> Result<T, TryRecvError> {
+        self.0.try_recv().map(MsgWrap::inner)
+    }
+
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<T, RecvTimeoutError> {
+        self.0.recv_timeout(timeout).map(MsgWrap::inner)
+    }
+
+    /// Each message's context is set on the
+    /// current span as it is yielded.
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.0.iter().map(MsgWrap::inner)
+    }
+
+    pub fn try_iter(&self) -> impl Iterator<Item = T> + '_ {
+        self.0.try_iter().map(MsgWrap::inner)
+    }
+}
@@ -0,0 +1,143 @@
+//! A codec adapter that carries the context with each frame.
+//! Each frame is written as:
+//! `[context length: u32][context][inner frame length: u32][inner frame]`
+//! so any inner codec gets propagation without calling
+//! `get_context_bytes` / `set_from_bytes` by hand.
+use crate::{Context, OpenSpanExt};
+use bytes::{Buf, BufMut, BytesMut};
+use std::{convert::TryFrom, io};
+use tokio_util::codec::{Decoder, Encoder};
+use tracing::Span;
+
+/// Size of each length prefix.
+const LEN_SIZE: usize = 4;
+/// Default limit on the size of a frame.
+const DEFAULT_MAX_FRAME_LENGTH: usize = 8 * 1024 * 1024;
+
+/// Wraps an inner codec. Encoding prepends the context of the
+/// current span. Decoding yields each item with a `frame` span
+/// that has the sender's context set.
+#[derive(Debug, Clone)]
+pub struct ContextCodec<C> {
+    inner: C,
+    max_frame_length: usize,
+}
+
+impl<C> ContextCodec<C> {
+    /// Wrap an inner codec.
+    pub fn new(inner: C) -> Self {
+        Self {
+            inner,
+            max_frame_length: DEFAULT_MAX_FRAME_LENGTH,
+        }
+    }
+
+    /// Frames larger than this are rejected with an
+    /// `InvalidData` error. Defaults to 8MB.
+    pub fn max_frame_length(mut self, max_frame_length: usize) -> Self {
+        self.max_frame_length = max_frame_length;
+        self
+    }
+
+    /// Get the inner codec.
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Read the length prefix at `at` if it is available.
+fn peek_len(src: &BytesMut, at: usize) -> Option<usize> {
+    let mut len = src.get(at..at + LEN_SIZE)?;
+    Some(len.get_u32() as usize)
+}
+
+impl<T, C> Encoder<T> for ContextCodec<C>
+where
+    C: Encoder<T>,
+{
+    type Error = C::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let context = Span::current().get_context_bytes();
+        let mut frame = BytesMut::new();
+        self.inner.encode(item, &mut frame)?;
+        let total = context.len() + frame.len() + LEN_SIZE * 2;
+        let too_long = || invalid_data("frame exceeds max frame length");
+        if total > self.max_frame_length {
+            return Err(too_long().into());
+        }
+        let context_len = u32::try_from(context.len()).map_err(|_| too_long())?;
+        let frame_len = u32::try_from(frame.len()).map_err(|_| too_long())?;
+        dst.reserve(total);
+        dst.put_u32(context_len);
+        dst.extend_from_slice(&context);
+        dst.put_u32(frame_len);
+        dst.extend_from_slice(&frame);
+        Ok(())
+    }
+}
+
+impl<C> Decoder for ContextCodec<C>
+where
+    C: Decoder,
+{
+    type Item = (C::Item, Span);
+    type Error = C::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        let context_len = match peek_len(src, 0) {
+            Some(len) => len,
+            None => return Ok(None),
+        };
+        let frame_at = LEN_SIZE + context_len;
+        if frame_at > self.max_frame_length {
+            return Err(invalid_data("frame exceeds max frame length").into());
+        }
+        let frame_len = match peek_len(src, frame_at) {
+            Some(len) => len,
+            None => {
+                src.reserve(frame_at + LEN_SIZE - src.len());
+                return Ok(None);
+            }
+        };
+        let total = frame_at + LEN_SIZE + frame_len;
+        if total > self.max_frame_length {
+            return Err(invalid_data("frame exceeds max frame length").into());
+        }
+        if src.len() < total {
+            src.reserve(total - src.len());
+            return Ok(None);
+        }
+
+        let mut frame = src.split_to(total);
+        frame.advance(LEN_SIZE);
+        let context = frame.split_to(context_len);
+        frame.advance(LEN_SIZE);
+        let item = match self.inner.decode(&mut frame)? {
+            Some(item) => item,
+            None => self
+                .inner
+                .decode_eof(&mut frame)?
+                .ok_or_else(|| invalid_data("inner codec did not decode the frame"))?,
+        };
+        if !frame.is_empty() {
+            return Err(invalid_data("inner codec left bytes in the frame").into());
+        }
+
+        // Error level so the span isn't filtered out
+        // and the sender's context isn't lost.
+        let span = tracing::error_span!("frame");
+        match Context::try_from_bytes(context.to_vec()) {
+            Ok(context) => span.set_context(context.with_message_size(total)),
+            Err(e) => tracing::error!(
+                msg = "Failed to deserialize tracing wire context of frame",
+                error = %e
+            ),
+        }
+        Ok(Some((item, span)))
+    }
+}
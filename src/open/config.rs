@@ -0,0 +1,128 @@
+use crate::errors::ConfigError;
+use std::{
+    convert::{TryFrom, TryInto},
+    ffi::OsString,
+    str::FromStr,
+};
+
+/// Configuration for open telemetry tracing.
+/// These can all be configured by setting the
+/// `OPEN_TEL='process:true,file:false'`
+/// or by passing a config to [crate::init_with_config].
+/// They all have default settings.
+#[derive(Debug, Clone)]
+pub struct Config {
+    /// Propagate the name of the process running
+    /// on the sender side of the boundary crossing
+    /// in the context and output when calling `spawn_context!()`.
+    /// [Default: false]
+    pub process: bool,
+    /// Propagate the name of the file and line
+    /// number of the sender side of the boundary crossing
+    /// in the context and output when calling `spawn_context!()`.
+    /// [Default: false]
+    pub file: bool,
+    /// Propagate the name of the span name
+    /// of the sender side of the boundary crossing
+    /// in the context and output when calling `spawn_context!()`.
+    /// [Default: true]
+    pub span_name: bool,
+    /// Require there to be a span enabled for the sending side of
+    /// the boundary crossing. [Default: true]
+    pub require_span: bool,
+    /// When a chain of links gets too long keep this many
+    /// of the first links in the chain. [Default: 4]
+    pub links_head: usize,
+    /// When a chain of links gets too long keep this many
+    /// of the last links in the chain. [Default: 12]
+    pub links_tail: usize,
+    /// Drop links from the middle of the chain until the
+    /// serialized context fits in this many bytes.
+    /// Zero means no limit. [Default: 0]
+    pub max_link_bytes: usize,
+    /// Copy the values of these span fields into the link
+    /// on the sender side of the boundary crossing.
+    /// Set with `link_fields: peer|msg_type`.
+    /// [Default: none]
+    pub link_fields: Vec<String>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            process: false,
+            file: false,
+            span_name: true,
+            require_span: true,
+            links_head: 4,
+            links_tail: 12,
+            max_link_bytes: 0,
+            link_fields: Vec::new(),
+        }
+    }
+}
+
+impl FromStr for Config {
+    type Err = ConfigError;
+
+    /// Parse a config from `key: value` pairs separated by commas.
+    /// Lists are separated by `|`.
+    /// Unknown keys and bad values are errors.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut config = Config::default();
+        for kv in s.split(',').map(str::trim).filter(|kv| !kv.is_empty()) {
+            let (key, value) = match kv.split(':').map(str::trim).collect::<Vec<_>>()[..] {
+                [key, value] => (key, value),
+                _ => return Err(ConfigError::Format(kv.to_string())),
+            };
+            match key {
+                "process" => config.process = parse(key, value)?,
+                "file" => config.file = parse(key, value)?,
+                "span_name" => config.span_name = parse(key, value)?,
+                "require_span" => config.require_span = parse(key, value)?,
+                "links_head" => config.links_head = parse(key, value)?,
+                "links_tail" => config.links_tail = parse(key, value)?,
+                "max_link_bytes" => config.max_link_bytes = parse(key, value)?,
+                "link_fields" => {
+                    config.link_fields = value
+                        .split('|')
+                        .map(str::trim)
+                        .filter(|f| !f.is_empty())
+                        .map(String::from)
+                        .collect()
+                }
+                _ => return Err(ConfigError::UnknownKey(key.to_string())),
+            }
+        }
+        Ok(config)
+    }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, ConfigError> {
+    value.parse().map_err(|_| ConfigError::BadValue {
+        key: key.to_string(),
+        value: value.to_string(),
+    })
+}
+
+impl Config {
+    /// Parse the config from the `OPEN_TEL` environment variable.
+    /// Uses the default config if it is not set.
+    pub fn from_env() -> Result<Self, ConfigError> {
+        std::env::var_os("OPEN_TEL").try_into()
+    }
+}
+
+impl TryFrom<Option<OsString>> for Config {
+    type Error = ConfigError;
+
+    /// Parse the config or use the
+    /// default config if there is none.
+    fn try_from(var: Option<OsString>) -> Result<Self, Self::Error> {
+        match var.map(OsString::into_string) {
+            Some(Ok(var)) => var.parse(),
+            Some(Err(_)) => Err(ConfigError::Format("OPEN_TEL is not unicode".to_string())),
+            None => Ok(Config::default()),
+        }
+    }
+}
@@ -8,6 +8,8 @@ use super::*;
 /// If you wish to avoid either of these propagations
 /// you can use `msg_wrap.without_context()` and
 /// `MsgWrap::from_no_context(t)` respectively.
+/// With `serde` it can be serialized and with
+/// `opentelemetry-on` it carries the context over the network.
 pub struct MsgWrap<T> {
     t: T,
     context: Option<Context>,
@@ -31,6 +33,12 @@ impl<T> MsgWrap<T> {
         }
         self.t
     }
+    /// Record the size of this message when the
+    /// context is propagated.
+    pub fn with_size(mut self, bytes: usize) -> Self {
+        self.context = self.context.map(|c| c.with_message_size(bytes));
+        self
+    }
     /// Get the inner type without propagating the context.
     pub fn without_context(self) -> T {
         self.t
@@ -64,6 +72,18 @@ impl<T> From<T> for MsgWrap<T> {
     }
 }
 
+impl<T> Clone for MsgWrap<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            t: self.t.clone(),
+            context: self.context.clone(),
+        }
+    }
+}
+
 impl<T> std::fmt::Debug for MsgWrap<T>
 where
     T: std::fmt::Debug,
@@ -72,3 +92,72 @@ where
         f.write_fmt(format_args!("{:?}", self.t))
     }
 }
+
+#[cfg(feature = "serde")]
+mod wire {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    #[cfg(feature = "opentelemetry-on")]
+    use serde_bytes::ByteBuf;
+
+    /// A message is serialized as the inner `T`
+    /// next to the context bytes.
+    /// Without `opentelemetry-on` there is no context
+    /// so only the `T` is sent.
+    #[derive(Serialize)]
+    struct WireMsgRef<'a, T> {
+        t: &'a T,
+        #[cfg(feature = "opentelemetry-on")]
+        c: Option<ByteBuf>,
+    }
+
+    #[derive(Deserialize)]
+    struct WireMsg<T> {
+        t: T,
+        #[cfg(feature = "opentelemetry-on")]
+        #[serde(default)]
+        c: Option<ByteBuf>,
+        // Accepted from senders that have the feature on.
+        #[cfg(not(feature = "opentelemetry-on"))]
+        #[serde(default)]
+        #[allow(dead_code)]
+        c: Option<serde::de::IgnoredAny>,
+    }
+
+    impl<T: Serialize> Serialize for MsgWrap<T> {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            WireMsgRef {
+                t: &self.t,
+                #[cfg(feature = "opentelemetry-on")]
+                c: self.context.as_ref().map(|c| ByteBuf::from(c.to_bytes())),
+            }
+            .serialize(serializer)
+        }
+    }
+
+    impl<'de, T: Deserialize<'de>> Deserialize<'de> for MsgWrap<T> {
+        #[cfg(feature = "opentelemetry-on")]
+        // A context that can't be read is logged and
+        // dropped so the message still gets through.
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let WireMsg { t, c } = WireMsg::deserialize(deserializer)?;
+            let context = c.and_then(|c| match Context::try_from_bytes(c.into_vec()) {
+                Ok(context) => Some(context),
+                Err(e) => {
+                    tracing::error!(
+                        msg = "Failed to deserialize tracing wire context of message",
+                        error = %e
+                    );
+                    None
+                }
+            });
+            Ok(Self { t, context })
+        }
+
+        #[cfg(not(feature = "opentelemetry-on"))]
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let WireMsg { t, .. } = WireMsg::deserialize(deserializer)?;
+            Ok(Self::from_no_context(t))
+        }
+    }
+}
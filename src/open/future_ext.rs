@@ -0,0 +1,48 @@
+use crate::{Context, OpenSpanExt};
+use pin_project_lite::pin_project;
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{self, Poll},
+};
+use tracing::Span;
+
+/// Run futures in a span that continues a propagated context.
+pub trait FutureExt: Future + Sized {
+    /// Set the context on the span when this future is first
+    /// polled and enter the span on every poll.
+    fn in_context(self, context: Context, span: Span) -> InContext<Self> {
+        InContext {
+            future: self,
+            context: Some(context),
+            span,
+        }
+    }
+}
+
+impl<F: Future> FutureExt for F {}
+
+pin_project! {
+    /// A future running in a span with a propagated context.
+    /// Created by [`FutureExt::in_context`].
+    #[derive(Debug)]
+    pub struct InContext<F> {
+        #[pin]
+        future: F,
+        context: Option<Context>,
+        span: Span,
+    }
+}
+
+impl<F: Future> Future for InContext<F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut task::Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Some(context) = this.context.take() {
+            this.span.set_context(context);
+        }
+        let _g = this.span.enter();
+        this.future.poll(cx)
+    }
+}
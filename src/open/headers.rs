@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+/// A carrier that context headers can be written into
+/// (e.g. the headers of an outgoing request).
+pub trait HeaderInjector {
+    /// Set a header on the carrier.
+    fn set(&mut self, key: &str, value: String);
+}
+
+/// A carrier that context headers can be read from
+/// (e.g. the headers of an incoming request).
+pub trait HeaderExtractor {
+    /// Get a header from the carrier.
+    fn get(&self, key: &str) -> Option<&str>;
+}
+
+impl<S: std::hash::BuildHasher> HeaderInjector for HashMap<String, String, S> {
+    fn set(&mut self, key: &str, value: String) {
+        self.insert(key.to_string(), value);
+    }
+}
+
+impl<S: std::hash::BuildHasher> HeaderExtractor for HashMap<String, String, S> {
+    fn get(&self, key: &str) -> Option<&str> {
+        HashMap::get(self, key).map(|v| v.as_str())
+    }
+}
+
+/// The header format used to propagate the trace context.
+/// Baggage is always propagated with the W3C `baggage` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Propagator {
+    /// W3C `traceparent` header (default).
+    #[default]
+    TraceContext,
+    /// B3 single `b3` header.
+    B3Single,
+    /// B3 multiple `x-b3-*` headers.
+    B3Multi,
+    /// Jaeger `uber-trace-id` header.
+    Jaeger,
+    /// Injects every format and extracts from
+    /// whichever format is present.
+    Composite,
+}
+
+#[cfg(feature = "opentelemetry-on")]
+pub(crate) use on::*;
+
+#[cfg(feature = "opentelemetry-on")]
+mod on {
+    use super::*;
+    use opentelemetry::api::{
+        self, trace::b3_propagator::B3Encoding, HttpTextFormat, TraceContextExt,
+    };
+
+    /// The header opentelemetry uses for correlation context.
+    const CORRELATION_HEADER: &str = "otcorrelations";
+    /// The W3C header for baggage.
+    const BAGGAGE_HEADER: &str = "baggage";
+    /// The Jaeger header for the trace context.
+    const JAEGER_HEADER: &str = "uber-trace-id";
+    /// Jaeger flag for a sampled trace.
+    const JAEGER_FLAG_SAMPLED: u8 = 0x01;
+    /// Jaeger flag for a debug trace.
+    const JAEGER_FLAG_DEBUG: u8 = 0x02;
+
+    /// Adapts our carriers to opentelemetry's and renames
+    /// the correlation context header to the W3C baggage header.
+    struct Carrier<'a, C: ?Sized>(&'a mut C);
+    struct CarrierRef<'a, C: ?Sized>(&'a C);
+
+    fn rename(key: &str) -> &str {
+        if key == CORRELATION_HEADER {
+            BAGGAGE_HEADER
+        } else {
+            key
+        }
+    }
+
+    impl<'a> api::Injector for Carrier<'a, dyn HeaderInjector + 'a> {
+        fn set(&mut self, key: &str, value: String) {
+            self.0.set(rename(key), value)
+        }
+    }
+
+    impl<'a> api::Extractor for CarrierRef<'a, dyn HeaderExtractor + 'a> {
+        fn get(&self, key: &str) -> Option<&str> {
+            self.0.get(rename(key))
+        }
+    }
+
+    /// Propagates the trace context using the Jaeger `uber-trace-id` header.
+    /// Format is `{trace_id}:{span_id}:{parent_span_id}:{flags}` in hex.
+    #[derive(Debug)]
+    struct JaegerPropagator;
+
+    impl HttpTextFormat for JaegerPropagator {
+        fn inject_context(&self, context: &api::Context, injector: &mut dyn api::Injector) {
+            let span_context = context.span().span_context();
+            if span_context.is_valid() {
+                let mut flags = 0;
+                if span_context.is_sampled() {
+                    flags |= JAEGER_FLAG_SAMPLED;
+                }
+                if span_context.is_debug() {
+                    flags |= JAEGER_FLAG_DEBUG;
+                }
+                injector.set(
+                    JAEGER_HEADER,
+                    format!(
+                        "{:032x}:{:016x}:0:{:x}",
+                        span_context.trace_id().to_u128(),
+                        span_context.span_id().to_u64(),
+                        flags
+                    ),
+                );
+            }
+        }
+
+        fn extract_with_context(
+            &self,
+            context: &api::Context,
+            extractor: &dyn api::Extractor,
+        ) -> api::Context {
+            match extractor.get(JAEGER_HEADER).and_then(parse_jaeger) {
+                Some(span_context) => context.with_remote_span_context(span_context),
+                None => context.clone(),
+            }
+        }
+    }
+
+    fn parse_jaeger(header: &str) -> Option<api::SpanContext> {
+        // Some clients url encode the header.
+        let header = header.replace("%3A", ":").replace("%3a", ":");
+        let parts = header.trim().split(':').collect::<Vec<_>>();
+        if parts.len() != 4 || parts[0].len() > 32 || parts[1].len() > 16 {
+            return None;
+        }
+        let trace_id = u128::from_str_radix(parts[0], 16).ok()?;
+        let span_id = u64::from_str_radix(parts[1], 16).ok()?;
+        let flags = u8::from_str_radix(parts[3], 16).ok()?;
+        let mut trace_flags = api::TRACE_FLAG_NOT_SAMPLED;
+        if flags & JAEGER_FLAG_SAMPLED != 0 {
+            trace_flags |= api::TRACE_FLAG_SAMPLED;
+        }
+        if flags & JAEGER_FLAG_DEBUG != 0 {
+            trace_flags |= api::TRACE_FLAG_DEBUG | api::TRACE_FLAG_SAMPLED;
+        }
+        let span_context = api::SpanContext::new(
+            api::TraceId::from_u128(trace_id),
+            api::SpanId::from_u64(span_id),
+            trace_flags,
+            true,
+        );
+        if span_context.is_valid() {
+            Some(span_context)
+        } else {
+            None
+        }
+    }
+
+    impl Propagator {
+        fn formats(self) -> Vec<Box<dyn HttpTextFormat>> {
+            match self {
+                Propagator::TraceContext => vec![Box::new(api::TraceContextPropagator::new())],
+                Propagator::B3Single => vec![Box::new(api::B3Propagator::with_encoding(
+                    B3Encoding::SingleHeader,
+                ))],
+                Propagator::B3Multi => vec![Box::new(api::B3Propagator::with_encoding(
+                    B3Encoding::MultipleHeader,
+                ))],
+                Propagator::Jaeger => vec![Box::new(JaegerPropagator)],
+                Propagator::Composite => vec![
+                    Box::new(api::TraceContextPropagator::new()),
+                    Box::new(api::B3Propagator::with_encoding(
+                        B3Encoding::SingleAndMultiHeader,
+                    )),
+                    Box::new(JaegerPropagator),
+                ],
+            }
+        }
+    }
+
+    /// Write the trace context and baggage into the headers.
+    pub(crate) fn inject(
+        propagator: Propagator,
+        context: &api::Context,
+        headers: &mut dyn HeaderInjector,
+    ) {
+        let mut carrier = Carrier(headers);
+        for format in propagator.formats() {
+            format.inject_context(context, &mut carrier);
+        }
+        api::CorrelationContextPropagator::new().inject_context(context, &mut carrier);
+    }
+
+    /// Read the trace context and baggage from the headers.
+    /// The first format with a valid trace context is used.
+    pub(crate) fn extract(propagator: Propagator, headers: &dyn HeaderExtractor) -> api::Context {
+        let carrier = CarrierRef(headers);
+        let empty = api::Context::new();
+        let context = propagator
+            .formats()
+            .into_iter()
+            .map(|format| format.extract_with_context(&empty, &carrier))
+            .find(|c| c.remote_span_context().is_some_and(|sc| sc.is_valid()))
+            .unwrap_or(empty);
+        api::CorrelationContextPropagator::new().extract_with_context(&context, &carrier)
+    }
+}
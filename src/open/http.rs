@@ -0,0 +1,135 @@
+//! Tower layers that continue traces across http requests.
+//! Put the [`ExtractLayer`] on servers and the [`InjectLayer`]
+//! on clients.
+use crate::{HeaderExtractor, HeaderInjector, OpenSpanExt, Propagator};
+use ::http::{header::HeaderName, HeaderMap, HeaderValue, Request};
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{instrument::Instrumented, Instrument, Span};
+
+impl HeaderInjector for HeaderMap {
+    fn set(&mut self, key: &str, value: String) {
+        // Headers that aren't valid http are skipped.
+        if let (Ok(key), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.insert(key, value);
+        }
+    }
+}
+
+impl HeaderExtractor for HeaderMap {
+    fn get(&self, key: &str) -> Option<&str> {
+        HeaderMap::get(self, key).and_then(|v| v.to_str().ok())
+    }
+}
+
+/// Runs each request in a span with the
+/// context extracted from the request headers.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ExtractLayer {
+    propagator: Propagator,
+}
+
+/// Injects the context of the current span
+/// into the headers of each request.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct InjectLayer {
+    propagator: Propagator,
+}
+
+/// The service created by [`ExtractLayer`].
+#[derive(Debug, Clone)]
+pub struct ExtractService<S> {
+    inner: S,
+    propagator: Propagator,
+}
+
+/// The service created by [`InjectLayer`].
+#[derive(Debug, Clone)]
+pub struct InjectService<S> {
+    inner: S,
+    propagator: Propagator,
+}
+
+impl ExtractLayer {
+    /// Extract the context with this header format.
+    pub fn new(propagator: Propagator) -> Self {
+        Self { propagator }
+    }
+}
+
+impl InjectLayer {
+    /// Inject the context with this header format.
+    pub fn new(propagator: Propagator) -> Self {
+        Self { propagator }
+    }
+}
+
+impl<S> Layer<S> for ExtractLayer {
+    type Service = ExtractService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ExtractService {
+            inner,
+            propagator: self.propagator,
+        }
+    }
+}
+
+impl<S> Layer<S> for InjectLayer {
+    type Service = InjectService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        InjectService {
+            inner,
+            propagator: self.propagator,
+        }
+    }
+}
+
+impl<S, B> Service<Request<B>> for ExtractService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Instrumented<S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        // A disabled span can't hold the extracted context
+        // so this is at error level to survive the default filter.
+        let span = tracing::error_span!(
+            "http request",
+            method = %request.method(),
+            path = %request.uri().path()
+        );
+        span.set_from_headers_with(self.propagator, request.headers());
+        let _g = span.enter();
+        self.inner.call(request).in_current_span()
+    }
+}
+
+impl<S, B> Service<Request<B>> for InjectService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        Span::current().get_context_headers_with(self.propagator, request.headers_mut());
+        self.inner.call(request)
+    }
+}
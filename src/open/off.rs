@@ -1,28 +1,40 @@
 use super::*;
 #[derive(Debug, Clone, Default)]
-pub struct Context;
+pub struct Context(());
 
-#[derive(Debug, Clone)]
-pub struct WireContext {
-    span_context: WireSpanContext,
-    links: Option<WireLinks>,
+impl Context {
+    pub fn new() -> Self {
+        Context(())
+    }
+    pub fn to_bytes(&self) -> Vec<u8> {
+        Vec::with_capacity(0)
+    }
+    pub fn try_from_bytes(_bytes: Vec<u8>) -> Result<Self, WireError> {
+        Ok(Context(()))
+    }
+    pub fn with_message_size(&self, _bytes: usize) -> Self {
+        Context(())
+    }
+    pub fn with_baggage(&self, _key: &str, _value: &str) -> Self {
+        Context(())
+    }
+    pub fn get_baggage(&self, _key: &str) -> Option<&str> {
+        None
+    }
+    pub fn baggage(&self) -> impl Iterator<Item = (&str, &str)> + '_ {
+        std::iter::empty()
+    }
 }
 
-#[derive(Debug, Clone, derive_more::From, derive_more::Into)]
-pub struct WireLinks(pub Vec<WireLink>);
-
-#[derive(Debug, Clone)]
-pub struct WireLink;
-
-#[derive(Debug, Clone)]
-pub struct WireSpanContext;
-
 impl OpenSpanExt for tracing::Span {
     fn get_current_context() -> Context {
-        Context
+        Context(())
     }
     fn get_context(&self) -> Context {
-        Context
+        Context(())
+    }
+    fn get_context_with(&self, _: &[(&str, &str)]) -> Context {
+        Context(())
     }
 
     fn get_current_bytes() -> Vec<u8> {
@@ -32,8 +44,13 @@ impl OpenSpanExt for tracing::Span {
     fn set_context(&self, _: Context) {}
 
     fn set_current_context(_: Context) {}
+
+    fn add_link(&self, _: &Context) {}
+
     fn set_current_bytes(_bytes: Vec<u8>) {}
 
+    fn set_baggage(&self, _key: &str, _value: &str) {}
+
     fn display_context(&self) -> String {
         String::with_capacity(0)
     }
@@ -44,4 +61,30 @@ impl std::fmt::Display for Context {
         Ok(())
     }
 }
-pub struct Config;
+
+/// Emit a tracing event with the context of a span.
+/// This is a no-op because the `opentelemetry-on`
+/// feature is disabled.
+#[macro_export]
+macro_rules! span_context {
+    (current, $lvl:expr) => {{
+        let _ = $lvl;
+    }};
+    ($span:expr, $lvl:expr) => {{
+        let _ = &$span;
+        let _ = $lvl;
+    }};
+    ($span:expr) => {{
+        let _ = &$span;
+    }};
+    () => {};
+}
+
+pub(crate) fn set_config(_: Config) -> Result<(), TracingError> {
+    Ok(())
+}
+
+#[doc(hidden)]
+pub fn should_run(_: &tracing::Span) -> bool {
+    false
+}
@@ -0,0 +1,68 @@
+use crate::{Context, OpenSpanExt};
+use tracing::Span;
+
+/// Link attribute holding the name of the spawned task or thread.
+const SPAWN_KEY: &str = "spawn";
+
+/// Capture the current context and record the spawn on the link.
+fn capture(name: &'static str) -> Context {
+    Span::current().get_context_with(&[(SPAWN_KEY, name)])
+}
+
+/// The span the child runs in named after the child.
+/// It's at the error level so it's enabled whenever the
+/// parent can be, otherwise the context would be dropped.
+fn child_span(name: &'static str, context: Context) -> Span {
+    let span = tracing::error_span!("spawn", otel.name = name);
+    span.set_context(context);
+    span
+}
+
+/// Spawn OS threads that continue the current context.
+pub mod thread {
+    use super::*;
+
+    /// Spawn a thread called `name` that runs `f` in a span
+    /// named `name` with the context of the current span.
+    pub fn spawn<F, T>(name: &'static str, f: F) -> std::thread::JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let context = capture(name);
+        std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || child_span(name, context).in_scope(f))
+            .expect("Failed to spawn thread")
+    }
+}
+
+/// Spawn tokio tasks that continue the current context.
+#[cfg(feature = "tasks")]
+pub mod task {
+    use super::*;
+    use std::future::Future;
+    use tracing::Instrument;
+
+    /// Spawn a task that runs `future` in a span named `name`
+    /// with the context of the current span.
+    pub fn spawn<F>(name: &'static str, future: F) -> tokio::task::JoinHandle<F::Output>
+    where
+        F: Future + Send + 'static,
+        F::Output: Send + 'static,
+    {
+        let context = capture(name);
+        tokio::spawn(async move { future.instrument(child_span(name, context)).await })
+    }
+
+    /// Spawn a blocking task that runs `f` in a span named `name`
+    /// with the context of the current span.
+    pub fn spawn_blocking<F, T>(name: &'static str, f: F) -> tokio::task::JoinHandle<T>
+    where
+        F: FnOnce() -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let context = capture(name);
+        tokio::task::spawn_blocking(move || child_span(name, context).in_scope(f))
+    }
+}
@@ -0,0 +1,104 @@
+//! Uses the public api that is available with and without the
+//! `opentelemetry-on` feature so that toggling the feature
+//! doesn't break call sites.
+//! Run with `cargo test --no-default-features` to check the off mode.
+use observability::{
+    errors::WireError, span_context, Config, Context, MsgWrap, OpenSpanExt, Propagator,
+};
+use std::collections::HashMap;
+use tracing::*;
+
+#[test]
+fn span_ext_api() {
+    observability::test_run_open().ok();
+    let span = debug_span!("parity");
+    let _g = span.enter();
+    span_context!();
+    span_context!(current, Level::DEBUG);
+    span_context!(span);
+    span_context!(span, Level::DEBUG);
+    let _: bool = observability::should_run(&span);
+
+    span.set_baggage("key", "value");
+    let context: Context = span.get_context();
+    let _: Option<&str> = context.get_baggage("key");
+    let _: Vec<(&str, &str)> = context.baggage().collect();
+    let context = context.with_baggage("other", "value");
+    let _: String = context.to_string();
+    let _: String = span.display_context();
+    let context = span.get_context_with(&[("peer", "localhost")]);
+    let _: Result<Context, WireError> = Context::try_from_bytes(context.to_bytes());
+
+    let bytes: Vec<u8> = span.get_context_bytes();
+    span.set_from_bytes(bytes.clone());
+    let _: Result<(), WireError> = span.try_set_from_bytes(bytes);
+    let _: Vec<u8> = Span::get_current_bytes();
+    Span::set_current_bytes(Vec::new());
+
+    let mut headers = HashMap::new();
+    span.get_context_headers(&mut headers);
+    span.get_context_headers_with(Propagator::Composite, &mut headers);
+    span.set_from_headers(&headers);
+    span.set_from_headers_with(Propagator::Jaeger, &headers);
+
+    span.set_context(context.clone());
+    span.add_link(&context);
+    span.set_contexts(vec![context.clone(), Context::default()]);
+    Span::set_current_context(Span::get_current_context());
+
+    let msg: MsgWrap<u32> = 1.into();
+    let _: (u32, Context) = msg.into_parts();
+    let _: u32 = MsgWrap::new(1, Context::new()).inner();
+    let _: u32 = MsgWrap::from_no_context(1).without_context();
+    let _: u32 = MsgWrap::new(1, Context::new().with_message_size(4))
+        .with_size(4)
+        .inner();
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&MsgWrap::from_no_context(1u32)).unwrap();
+        let _: u32 = serde_json::from_str::<MsgWrap<u32>>(&json)
+            .unwrap()
+            .without_context();
+    }
+
+    let _: u32 = observability::thread::spawn("parity", || 1).join().unwrap();
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn future_ext_api() {
+    use observability::FutureExt;
+    let _: u32 = async { 1 }
+        .in_context(Context::new(), debug_span!("parity"))
+        .await;
+}
+
+#[test]
+fn config_api() {
+    let config = Config {
+        process: false,
+        file: false,
+        span_name: true,
+        require_span: true,
+        links_head: 4,
+        links_tail: 12,
+        max_link_bytes: 0,
+        link_fields: vec![],
+    };
+    let default = Config::default();
+    assert_eq!(config.span_name, default.span_name);
+    assert_eq!(config.links_tail, default.links_tail);
+    let _: Result<Config, observability::errors::ConfigError> = "file: true".parse();
+    let _: fn(observability::Output, Config) -> Result<(), _> = observability::init_with_config;
+}
+
+#[cfg(feature = "channels")]
+#[tokio::test(threaded_scheduler)]
+async fn channel_api() {
+    use observability::channel::{mpsc, oneshot};
+    let (mut tx, mut rx) = mpsc::channel(1);
+    tx.send(1).await.unwrap();
+    assert_eq!(rx.recv().await, Some(1));
+    let (tx, rx) = oneshot::channel();
+    tx.send(1).unwrap();
+    assert_eq!(rx.await.unwrap(), 1);
+}
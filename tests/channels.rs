@@ -0,0 +1,161 @@
+#![cfg(all(
+    feature = "opentelemetry-on",
+    any(
+        feature = "channels",
+        feature = "std-channels",
+        feature = "crossbeam-channels"
+    )
+))]
+#[cfg(feature = "channels")]
+use observability::channel::{broadcast, mpsc, oneshot, watch};
+use observability::OpenSpanExt;
+use tracing::*;
+
+fn sent_from(span: &Span, name: &str) -> bool {
+    span.display_context()
+        .contains(&format!("-> span: {};", name))
+}
+
+#[cfg(feature = "channels")]
+#[tokio::test(threaded_scheduler)]
+async fn broadcast_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let (tx, mut rx1) = broadcast::channel(10);
+    let mut rx2 = tx.subscribe();
+    error_span!("sender").in_scope(|| tx.send(1).unwrap());
+
+    // Each receiver gets the senders context.
+    let span = error_span!("receiver 1");
+    let _g = span.enter();
+    assert_eq!(rx1.recv().await.unwrap(), 1);
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+    drop(_g);
+
+    let span = error_span!("receiver 2");
+    let _g = span.enter();
+    assert_eq!(rx2.try_recv().unwrap(), 1);
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+}
+
+#[cfg(feature = "channels")]
+#[tokio::test(threaded_scheduler)]
+async fn watch_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let (tx, mut rx) = error_span!("init").in_scope(|| watch::channel(0));
+    error_span!("sender").in_scope(|| tx.broadcast(1).unwrap());
+
+    let span = error_span!("receiver");
+    let _g = span.enter();
+    assert_eq!(rx.try_recv(), Some(1));
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+    // No change since the last receive.
+    assert_eq!(rx.try_recv(), None);
+
+    drop(_g);
+    drop(rx);
+    assert_eq!(tx.broadcast(2), Err(2));
+}
+
+#[cfg(feature = "channels")]
+#[tokio::test(threaded_scheduler)]
+async fn unbounded_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    error_span!("sender").in_scope(|| tx.send(1).unwrap());
+
+    let span = error_span!("receiver");
+    let _g = span.enter();
+    assert_eq!(rx.try_recv().unwrap(), 1);
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+}
+
+#[cfg(feature = "channels")]
+#[tokio::test(threaded_scheduler)]
+async fn mpsc_api_test() {
+    use std::time::Duration;
+    use tokio::stream::StreamExt;
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let (mut tx, mut rx) = mpsc::channel(1);
+    error_span!("sender").in_scope(|| tx.try_send(1).unwrap());
+    assert!(matches!(
+        tx.try_send(2),
+        Err(tokio::sync::mpsc::error::TrySendError::Full(2))
+    ));
+    assert!(matches!(
+        tx.send_timeout(2, Duration::from_millis(10)).await,
+        Err(tokio::sync::mpsc::error::SendTimeoutError::Timeout(2))
+    ));
+
+    let span = error_span!("receiver");
+    let _g = span.enter();
+    assert_eq!(rx.next().await, Some(1));
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+    drop(_g);
+
+    rx.close();
+    assert!(rx.try_recv().is_err());
+
+    // A sender can't reserve a slot once the receiver is gone.
+    let (mut tx, rx) = mpsc::channel::<u8>(1);
+    drop(rx);
+    assert!(tokio::future::poll_fn(|cx| tx.poll_ready(cx))
+        .await
+        .is_err());
+}
+
+#[cfg(feature = "channels")]
+#[tokio::test(threaded_scheduler)]
+async fn oneshot_api_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let (tx, mut rx) = oneshot::channel();
+    assert!(!tx.is_closed());
+    error_span!("sender").in_scope(|| tx.send(1).unwrap());
+
+    let span = error_span!("receiver");
+    let _g = span.enter();
+    assert_eq!(rx.try_recv().unwrap(), 1);
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+    drop(_g);
+
+    let (tx, mut rx) = oneshot::channel::<()>();
+    rx.close();
+    assert!(tx.is_closed());
+}
+
+#[cfg(feature = "std-channels")]
+#[test]
+fn std_mpsc_test() {
+    use observability::channel::std_mpsc;
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let (tx, rx) = std_mpsc::sync_channel(1);
+    let jh = std::thread::spawn(move || {
+        error_span!("sender").in_scope(|| tx.send(1).unwrap());
+        tx.try_send(2)
+    });
+    assert!(jh.join().unwrap().is_err());
+
+    let span = error_span!("receiver");
+    let _g = span.enter();
+    assert_eq!(rx.iter().collect::<Vec<_>>(), vec![1]);
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+}
+
+#[cfg(feature = "crossbeam-channels")]
+#[test]
+fn crossbeam_test() {
+    use observability::channel::crossbeam;
+    use std::time::Duration;
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let (tx, rx) = crossbeam::bounded(1);
+    let jh = std::thread::spawn(move || {
+        error_span!("sender").in_scope(|| tx.send(1).unwrap());
+        tx.send_timeout(2, Duration::from_millis(10))
+    });
+    assert!(jh.join().unwrap().is_err());
+    assert_eq!(rx.len(), 1);
+
+    let span = error_span!("receiver");
+    let _g = span.enter();
+    assert_eq!(rx.recv_timeout(Duration::from_millis(10)), Ok(1));
+    assert!(sent_from(&span, "sender"), "{}", span.display_context());
+}
@@ -0,0 +1,43 @@
+#![cfg(all(feature = "codec", feature = "opentelemetry-on"))]
+use bytes::{BufMut, BytesMut};
+use observability::{codec::ContextCodec, OpenSpanExt};
+use tokio_util::codec::{Decoder, Encoder, LinesCodec, LinesCodecError};
+use tracing::*;
+
+#[test]
+fn codec_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let mut codec = ContextCodec::new(LinesCodec::new());
+    let mut buf = BytesMut::new();
+    error_span!("sender").in_scope(|| {
+        codec.encode("ping", &mut buf).unwrap();
+        codec.encode("pong", &mut buf).unwrap();
+    });
+
+    // Partial frames wait for more bytes.
+    let mut partial = buf.split_to(buf.len() / 2 + 3);
+    assert!(codec.decode(&mut partial).unwrap().is_some());
+    assert!(codec.decode(&mut partial).unwrap().is_none());
+    partial.unsplit(buf);
+    let (line, span) = codec.decode(&mut partial).unwrap().unwrap();
+    assert_eq!(line, "pong");
+    let display = span.display_context();
+    assert!(display.contains("-> span: sender;"), "{}", display);
+    assert!(partial.is_empty());
+
+    // Bytes the inner codec doesn't consume are an error.
+    let mut buf = BytesMut::new();
+    buf.put_u32(0);
+    buf.put_u32(10);
+    buf.put_slice(b"ping\nextra");
+    match codec.decode(&mut buf) {
+        Err(LinesCodecError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidData),
+        r => panic!(
+            "expected invalid data, got {:?}",
+            r.map(|o| o.map(|(line, _)| line))
+        ),
+    }
+
+    let mut codec = ContextCodec::new(LinesCodec::new()).max_frame_length(8);
+    assert!(codec.encode("too long", &mut BytesMut::new()).is_err());
+}
@@ -0,0 +1,70 @@
+use observability::{errors::ConfigError, Config};
+
+#[test]
+fn parse_config() {
+    let config: Config = "process: true, links_tail: 3, link_fields: peer | msg,"
+        .parse()
+        .unwrap();
+    assert!(config.process);
+    assert!(!config.file);
+    assert_eq!(config.links_tail, 3);
+    assert_eq!(config.link_fields, vec!["peer", "msg"]);
+
+    assert_eq!(
+        "procces: true".parse::<Config>().unwrap_err(),
+        ConfigError::UnknownKey("procces".to_string())
+    );
+    assert_eq!(
+        "file: yes".parse::<Config>().unwrap_err(),
+        ConfigError::BadValue {
+            key: "file".to_string(),
+            value: "yes".to_string()
+        }
+    );
+    assert_eq!(
+        "file".parse::<Config>().unwrap_err(),
+        ConfigError::Format("file".to_string())
+    );
+}
+
+#[test]
+fn config_from_var() {
+    use std::convert::TryFrom;
+    use std::ffi::OsString;
+    assert!(!Config::try_from(None).unwrap().file);
+    assert!(
+        Config::try_from(Some(OsString::from("file: true")))
+            .unwrap()
+            .file
+    );
+    // Bad input is an error instead of the default config.
+    assert_eq!(
+        Config::try_from(Some(OsString::from("file: yes"))).unwrap_err(),
+        ConfigError::BadValue {
+            key: "file".to_string(),
+            value: "yes".to_string()
+        }
+    );
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn link_fields() {
+    use observability::{OpenSpanExt, Output};
+    use tracing::*;
+    let config = Config {
+        file: true,
+        link_fields: vec!["peer".to_string(), "late".to_string()],
+        ..Config::default()
+    };
+    observability::init_with_config(Output::OpenTel, config).unwrap();
+    let span = error_span!("send", peer = "10.0.0.1", other = 1, late = field::Empty);
+    span.record("late", 5);
+    let receiver = error_span!("recv");
+    receiver.set_context(span.get_context());
+    let context = receiver.get_context().to_string();
+    assert!(context.contains("peer: 10.0.0.1;"), "{}", context);
+    assert!(context.contains("late: 5;"), "{}", context);
+    assert!(context.contains("file: tests/config.rs"), "{}", context);
+    assert!(!context.contains("other"), "{}", context);
+}
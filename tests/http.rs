@@ -0,0 +1,42 @@
+#![cfg(all(feature = "http", feature = "opentelemetry-on"))]
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Client, Request, Response, Server,
+};
+use observability::{
+    http::{ExtractLayer, InjectLayer},
+    OpenSpanExt,
+};
+use std::convert::Infallible;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::*;
+
+async fn handle(_: Request<Body>) -> Result<Response<Body>, Infallible> {
+    Ok(Response::new(Body::from(Span::current().display_context())))
+}
+
+#[tokio::test(threaded_scheduler)]
+async fn http_layers_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+
+    let make_service = make_service_fn(|_| async {
+        Ok::<_, Infallible>(ExtractLayer::default().layer(service_fn(handle)))
+    });
+    let server = Server::bind(&([127, 0, 0, 1], 0).into()).serve(make_service);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+
+    let mut client = InjectLayer::default().layer(Client::new());
+    let request = Request::get(format!("http://{}/ping", addr))
+        .body(Body::empty())
+        .unwrap();
+    let span = error_span!("http client");
+    let response = span.in_scope(|| client.call(request)).await.unwrap();
+    let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+    let display = String::from_utf8(body.to_vec()).unwrap();
+
+    // Headers only carry the trace id and baggage.
+    let trace_id = |display: &str| display.split(" ->").next().unwrap().to_string();
+    assert_eq!(trace_id(&display), trace_id(&span.display_context()));
+}
@@ -0,0 +1,28 @@
+use observability::metrics;
+
+metrics!(Calls, counter Made{peer, zome});
+
+// Needs its own test binary because metrics being
+// on and the label cap are global.
+#[test]
+fn label_cap_test() {
+    // Series taken before metrics are on still count after.
+    let early = Calls::with_labels(Calls::Made, &["a", "z1"]);
+    early.count(1);
+    metrics::init();
+    early.count(2);
+    assert_eq!(
+        Calls::with_labels(Calls::Made, &["a", "z1"]).value(),
+        metrics::Value::Counter(2)
+    );
+    Calls::with_labels(Calls::Made, &["b", "z1"]).count(1);
+
+    // Past the cap everything goes to the overflow series.
+    metrics::set_label_cap(3);
+    Calls::with_labels(Calls::Made, &["c", "z1"]).count(1);
+    Calls::with_labels(Calls::Made, &["d", "z1"]).count(1);
+    Calls::with_labels(Calls::Made, &["e", "z1"]).count(1);
+    let overflow = r#"{peer="overflow",zome="overflow"}"#;
+    assert!(Calls::iter().any(|(_, l, n)| l.to_string() == overflow && n == 2));
+    assert_eq!(Calls::iter().count(), 5);
+}
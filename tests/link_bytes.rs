@@ -0,0 +1,36 @@
+#![cfg(feature = "opentelemetry-on")]
+use observability::{Config, OpenSpanExt, Output};
+use tracing::*;
+
+const MAX_BYTES: usize = 860;
+
+// Needs its own test binary because the config is global.
+#[test]
+fn max_link_bytes_test() {
+    let config = Config {
+        max_link_bytes: MAX_BYTES,
+        ..Config::default()
+    };
+    observability::init_with_config(Output::OpenTel, config).unwrap();
+    let mut bytes = error_span!("hop")
+        .get_context_with(&[("hop", "0")])
+        .to_bytes();
+    for i in 1..50 {
+        let span = error_span!("hop");
+        span.set_from_bytes(bytes);
+        bytes = span.get_context_with(&[("hop", &i.to_string())]).to_bytes();
+        assert!(bytes.len() <= MAX_BYTES, "hop {}: {} bytes", i, bytes.len());
+    }
+    let span = error_span!("hop");
+    span.set_from_bytes(bytes);
+    let context = span.get_context().to_string();
+    // Links are dropped from the tail side of the middle
+    // until the context fits so the head is kept whole.
+    let hops = |r: std::ops::Range<usize>| {
+        r.map(|i| format!(" -> span: hop; hop: {};", i))
+            .collect::<String>()
+    };
+    let expected = format!("{} -> elided: 43;{}", hops(0..4), hops(47..50));
+    let (_, links) = context.split_at(context.find(" ->").unwrap());
+    assert_eq!(links, expected);
+}
@@ -0,0 +1,457 @@
+use observability::metrics;
+use observability::metrics::{Histogram, Labels, Value};
+
+metrics!(Net, counter Sent, gauge Open, histogram Latency, Received);
+metrics!(
+    /// Sets can share a module.
+    pub(crate) Disk,
+    /// Bytes written
+    /// to disk.
+    counter Written,
+    gauge Files,
+);
+// Declared but never written to.
+metrics!(Untouched, counter Never, histogram Idle);
+
+#[test]
+fn metric_kinds_test() {
+    metrics::init();
+    Net::count(Net::Sent, 2);
+    Net::count_silent(Net::Received, 1);
+    assert_eq!(Net::get(Net::Sent), 2);
+    assert_eq!(Net::value(Net::Received), Value::Counter(1));
+
+    Net::set(Net::Open, 1);
+    Net::dec(Net::Open);
+    Net::dec(Net::Open);
+    assert_eq!(Net::value(Net::Open), Value::Gauge(-1));
+    assert_eq!(Net::get(Net::Open), 0);
+    Net::inc(Net::Open);
+    Net::inc(Net::Open);
+    assert_eq!(Net::value(Net::Open), Value::Gauge(1));
+
+    for i in 1..=100u64 {
+        Net::record(Net::Latency, i);
+    }
+    let h = match Net::value(Net::Latency) {
+        Value::Histogram(h) => h,
+        v => panic!("{:?}", v),
+    };
+    assert_eq!(h.count, 100);
+    assert_eq!(h.sum, 5050);
+    assert_eq!(h.buckets[Histogram::bucket(100)], 37);
+    assert!((32..=63).contains(&h.quantile(0.5)), "{}", h.quantile(0.5));
+    assert!((64..=127).contains(&h.quantile(0.99)));
+    assert_eq!(Net::get(Net::Latency), 100);
+    assert_eq!(Net::iter().count(), 4);
+    assert!(Net::iter().all(|(_, labels, _)| labels.is_empty()));
+}
+
+#[test]
+fn histogram_buckets_test() {
+    assert_eq!(Histogram::bucket(0), 0);
+    assert_eq!(Histogram::bucket(1), 1);
+    assert_eq!(Histogram::bucket(3), 2);
+    assert_eq!(Histogram::bucket(u64::MAX), 64);
+    assert_eq!(Histogram::bucket_bound(2), 3);
+    assert_eq!(Histogram::bucket_bound(64), u64::MAX);
+    let h = Histogram {
+        count: 0,
+        sum: 0,
+        buckets: vec![0; metrics::BUCKETS],
+    };
+    assert_eq!(h.quantile(0.5), 0);
+}
+
+#[test]
+fn registry_test() {
+    metrics::init();
+    Net::count(Net::Received, 1);
+    let snapshots = metrics::snapshot_all();
+    let set = |name: &str| snapshots.iter().find(|s| s.set == name).unwrap();
+    assert!(matches!(
+        set("Net").value("Received", &[]),
+        Some(Value::Counter(n)) if *n >= 1
+    ));
+    // Every declared set is there even if it's never used.
+    assert_eq!(
+        set("Untouched").value("Never", &[]),
+        Some(&Value::Counter(0))
+    );
+    set("Boundary");
+    let text = metrics::prometheus();
+    assert!(text.contains("\nuntouched_never_total 0\n"), "{}", text);
+    assert!(text.contains("\nuntouched_idle_count 0\n"), "{}", text);
+
+    let mut path = std::env::temp_dir();
+    path.push(format!("all_metrics_{}.csv", std::process::id()));
+    metrics::save_all_csv(&path);
+    let csv = std::fs::read_to_string(&path).unwrap();
+    std::fs::remove_file(&path).ok();
+    let header = csv.lines().next().unwrap();
+    assert!(header.contains("Net::Received,"), "{}", header);
+    assert!(header.contains("Net::Latency_p99,"), "{}", header);
+    assert!(header.contains("Untouched::Never,"), "{}", header);
+    metrics::print_all();
+}
+
+#[test]
+fn many_sets_test() {
+    metrics::init();
+    Disk::count(Disk::Written, 3);
+    assert_eq!(Disk::get(Disk::Written), 3);
+    assert_eq!(Disk::iter().count(), 2);
+    let text = Disk::prometheus();
+    assert!(text.contains("# HELP disk_written_total Bytes written to disk.\n"));
+    assert!(text.contains("# HELP disk_files Disk::Files\n"));
+    assert!(!text.contains("net_"));
+}
+
+// Two sets with the same name in different modules.
+mod first {
+    observability::metrics!(Twin, counter Sent);
+}
+mod second {
+    observability::metrics!(Twin, counter Sent);
+}
+
+#[test]
+fn same_name_test() {
+    metrics::init();
+    first::Twin::count(first::Twin::Sent, 1);
+    second::Twin::count(second::Twin::Sent, 2);
+    let text = metrics::prometheus();
+    assert!(
+        text.contains("\nmetrics_first_twin_sent_total 1\n"),
+        "{}",
+        text
+    );
+    assert!(
+        text.contains("\nmetrics_second_twin_sent_total 2\n"),
+        "{}",
+        text
+    );
+    assert!(!text.contains("\ntwin_sent_total"), "{}", text);
+    // Every family is only described once.
+    let mut types: Vec<_> = text.lines().filter(|l| l.starts_with("# TYPE")).collect();
+    let len = types.len();
+    types.sort_unstable();
+    types.dedup();
+    assert_eq!(types.len(), len);
+
+    let snapshots = metrics::snapshot_all();
+    let sent = |set: &str| {
+        snapshots
+            .iter()
+            .find(|s| s.set == set)
+            .and_then(|s| s.value("Sent", &[]).cloned())
+    };
+    assert_eq!(sent("metrics::first::Twin"), Some(Value::Counter(1)));
+    assert_eq!(sent("metrics::second::Twin"), Some(Value::Counter(2)));
+    assert!(!snapshots.iter().any(|s| s.set == "Twin"));
+    assert_eq!(first::Twin::snapshot().set, "metrics::first::Twin");
+}
+
+// Sets in their own modules so tests don't share values.
+mod labeled {
+    use super::*;
+
+    metrics!(Calls, counter Made{peer, zome}, histogram Took{zome});
+
+    #[test]
+    fn labeled_metrics_test() {
+        metrics::init();
+        Calls::count(Calls::Made, 1);
+        Calls::with_labels(Calls::Made, &["a", "z1"]).count(2);
+        Calls::with_labels(Calls::Made, &["a", "z1"]).count(3);
+        Calls::with_labels(Calls::Made, &["b", "z1"]).count(1);
+        Calls::with_labels(Calls::Took, &["z1"]).record(10);
+        let took = Calls::with_labels(Calls::Took, &["z2"]);
+        took.record(20);
+        took.record(30);
+
+        let made: Vec<_> = Calls::iter()
+            .filter(|(m, _, _)| matches!(m, Calls::Made))
+            .map(|(_, l, n)| (l.to_string(), n))
+            .collect();
+        assert_eq!(
+            made,
+            vec![
+                ("".to_string(), 1),
+                (r#"{peer="a",zome="z1"}"#.to_string(), 5),
+                (r#"{peer="b",zome="z1"}"#.to_string(), 1),
+            ]
+        );
+        let z2 = Labels(vec![("zome", "z2".to_string())]);
+        assert!(Calls::values()
+            .any(|(_, l, v)| l == z2
+                && matches!(v, Value::Histogram(h) if h.count == 2 && h.sum == 50)));
+    }
+}
+
+mod prometheus {
+    use super::*;
+
+    metrics!(PromTest, counter Sent{peer}, gauge Open, histogram Latency);
+
+    #[test]
+    fn prometheus_test() {
+        metrics::init();
+        PromTest::with_labels(PromTest::Sent, &["a\"b"]).count(2);
+        PromTest::set(PromTest::Open, -3);
+        PromTest::record(PromTest::Latency, 5);
+        let text = PromTest::prometheus();
+        for line in &[
+            "# HELP prom_test_sent_total PromTest::Sent",
+            "# TYPE prom_test_sent_total counter",
+            "prom_test_sent_total 0",
+            r#"prom_test_sent_total{peer="a\"b"} 2"#,
+            "# TYPE prom_test_open gauge",
+            "prom_test_open -3",
+            "# TYPE prom_test_latency histogram",
+            r#"prom_test_latency_bucket{le="3"} 0"#,
+            r#"prom_test_latency_bucket{le="7"} 1"#,
+            r#"prom_test_latency_bucket{le="+Inf"} 1"#,
+            "prom_test_latency_sum 5",
+            "prom_test_latency_count 1",
+        ] {
+            assert!(text.lines().any(|l| l == *line), "{}\n{}", line, text);
+        }
+        assert!(metrics::prometheus().contains(&text));
+    }
+
+    #[cfg(feature = "metrics-endpoint")]
+    #[test]
+    fn endpoint_test() {
+        use std::io::{Read, Write};
+        metrics::init();
+        let server = metrics::serve_prometheus(0).unwrap();
+        let addr = server.addr();
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /metrics HTTP/1.1\r\nHost: localhost\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains("# TYPE prom_test_open gauge"));
+        assert!(response.contains("# TYPE untouched_never_total counter"));
+
+        // Dropping the server frees the port.
+        drop(server);
+        std::net::TcpListener::bind(addr).unwrap();
+    }
+}
+
+#[cfg(feature = "otlp-metrics")]
+mod otlp {
+    use super::*;
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    metrics!(OtlpTest, counter Sent{peer}, gauge Open, histogram Latency);
+
+    /// Accept one export and return its body.
+    fn collector() -> (String, std::thread::JoinHandle<serde_json::Value>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}/v1/metrics", listener.local_addr().unwrap());
+        let jh = std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut request = String::new();
+            reader.read_line(&mut request).unwrap();
+            assert!(request.starts_with("POST /v1/metrics "), "{}", request);
+            let mut len = 0;
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                if let Some(n) = header.to_lowercase().strip_prefix("content-length:") {
+                    len = n.trim().parse().unwrap();
+                }
+                header.clear();
+            }
+            let mut body = vec![0; len];
+            reader.read_exact(&mut body).unwrap();
+            stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+                .unwrap();
+            serde_json::from_slice(&body).unwrap()
+        });
+        (endpoint, jh)
+    }
+
+    #[test]
+    fn otlp_test() {
+        metrics::init();
+        let started = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        OtlpTest::with_labels(OtlpTest::Sent, &["a"]).count(2);
+        OtlpTest::set(OtlpTest::Open, 3);
+        OtlpTest::record(OtlpTest::Latency, 5);
+
+        let (endpoint, jh) = collector();
+        let export =
+            metrics::start_otlp_export(&endpoint, std::time::Duration::from_secs(60)).unwrap();
+        // Dropping sends the final export.
+        drop(export);
+        let body = jh.join().unwrap();
+
+        let resource = &body["resourceMetrics"][0];
+        let attributes = resource["resource"]["attributes"].as_array().unwrap();
+        assert!(attributes.iter().any(|a| a["key"] == "service.name"));
+        // Sets that share a name are still separate scopes.
+        let scopes: Vec<_> = resource["scopeMetrics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|s| s["scope"]["name"].as_str().unwrap())
+            .collect();
+        assert!(scopes.contains(&"metrics::first::Twin"), "{:?}", scopes);
+        assert!(scopes.contains(&"metrics::second::Twin"), "{:?}", scopes);
+        let scope = resource["scopeMetrics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["scope"]["name"] == "OtlpTest")
+            .unwrap();
+        let metric = |name: &str| {
+            scope["metrics"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|m| m["name"] == name)
+                .unwrap()
+                .clone()
+        };
+        let sent = &metric("sent")["sum"];
+        assert_eq!(sent["isMonotonic"], true);
+        let point = &sent["dataPoints"][1];
+        assert_eq!(point["asInt"], "2");
+        // Cumulative since metrics were turned on not the first export.
+        let start: u128 = point["startTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(start <= started);
+        assert_eq!(point["attributes"][0]["key"], "peer");
+        assert_eq!(point["attributes"][0]["value"]["stringValue"], "a");
+        assert_eq!(metric("open")["gauge"]["dataPoints"][0]["asInt"], "3");
+        let latency = &metric("latency")["histogram"]["dataPoints"][0];
+        assert_eq!(latency["count"], "1");
+        assert_eq!(latency["bucketCounts"][3], "1");
+
+        // Resetting the set starts the cumulative counts again.
+        OtlpTest::reset();
+        let (endpoint, jh) = collector();
+        metrics::export_otlp(&endpoint).unwrap();
+        let body = jh.join().unwrap();
+        let scope = body["resourceMetrics"][0]["scopeMetrics"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|s| s["scope"]["name"] == "OtlpTest")
+            .unwrap()
+            .clone();
+        let point = &scope["metrics"][0]["sum"]["dataPoints"][0];
+        let reset_start: u128 = point["startTimeUnixNano"]
+            .as_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert!(reset_start > started);
+        assert_eq!(point["asInt"], "0");
+
+        let e = metrics::export_otlp("https://localhost").unwrap_err();
+        assert!(e.to_string().contains("https"), "{}", e);
+    }
+}
+
+mod sampler {
+    use super::*;
+
+    metrics!(Soak, counter Msgs, gauge Peers);
+
+    #[test]
+    fn sample_csv_test() {
+        metrics::init();
+        Soak::count(Soak::Msgs, 10);
+        let mut path = std::env::temp_dir();
+        path.push(format!("sampled_metrics_{}.csv", std::process::id()));
+        std::fs::remove_file(&path).ok();
+
+        let sampler = metrics::sample_csv(&path, std::time::Duration::from_millis(20)).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(50));
+        Soak::count(Soak::Msgs, 10);
+        Soak::set(Soak::Peers, 2);
+        // Dropping writes the last row.
+        drop(sampler);
+
+        let csv = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        let mut lines = csv.lines();
+        let header: Vec<_> = lines.next().unwrap().split(',').collect();
+        assert_eq!(header[0], "time");
+        let msgs = header.iter().position(|k| *k == "Soak::Msgs").unwrap();
+        assert_eq!(header[msgs + 1], "Soak::Msgs_per_sec");
+        let rows: Vec<Vec<_>> = lines
+            .filter(|l| !l.starts_with("time,"))
+            .map(|l| l.split(',').collect())
+            .collect();
+        assert!(rows.len() >= 3, "{}", csv);
+        assert_eq!(rows[0][msgs], "10");
+        assert_eq!(rows[0][msgs + 1], "");
+        assert_eq!(rows.last().unwrap()[msgs], "20");
+        assert!(rows[1..]
+            .iter()
+            .any(|r| r[msgs + 1].parse::<f64>().unwrap() > 0.0));
+    }
+}
+
+mod snapshot {
+    use super::*;
+
+    metrics!(Window, counter Handled{kind}, gauge Busy, histogram Took);
+
+    #[test]
+    fn snapshot_test() {
+        metrics::init();
+        Window::with_labels(Window::Handled, &["get"]).count(2);
+        Window::set(Window::Busy, 4);
+        Window::record(Window::Took, 5);
+        let before = Window::snapshot();
+        assert_eq!(before.value("Handled", &["get"]), Some(&Value::Counter(2)));
+
+        Window::with_labels(Window::Handled, &["get"]).count(3);
+        Window::with_labels(Window::Handled, &["put"]).count(1);
+        Window::record(Window::Took, 100);
+        let delta = Window::delta(&before);
+        assert_eq!(delta.value("Handled", &["get"]), Some(&Value::Counter(3)));
+        assert_eq!(delta.value("Handled", &["put"]), Some(&Value::Counter(1)));
+        assert_eq!(delta.value("Busy", &[]), Some(&Value::Gauge(4)));
+        match delta.value("Took", &[]) {
+            Some(Value::Histogram(h)) => assert_eq!((h.count, h.sum), (1, 100)),
+            v => panic!("{:?}", v),
+        }
+
+        let reset = Window::reset();
+        assert_eq!(reset.value("Handled", &["get"]), Some(&Value::Counter(5)));
+        assert_eq!(
+            Window::with_labels(Window::Handled, &["get"]).value(),
+            Value::Counter(0)
+        );
+        assert_eq!(Window::get(Window::Took), 0);
+        assert_eq!(Window::value(Window::Busy), Value::Gauge(4));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn snapshot_serde_test() {
+        metrics::init();
+        Window::with_labels(Window::Handled, &["serde"]).count(1);
+        let snapshot = Window::snapshot();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let back: metrics::Snapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, snapshot);
+    }
+}
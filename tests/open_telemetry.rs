@@ -79,3 +79,224 @@ async fn across_thread(mut rx: mpsc::Receiver<Context>, mut tx: mpsc::Sender<Con
         tx.send(context).await.unwrap();
     }
 }
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn baggage_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let span = error_span!("span a");
+    span.set_baggage("tenant", "tenant_a");
+    let context = span.get_context();
+    assert_eq!(context.get_baggage("tenant"), Some("tenant_a"));
+
+    let span_b = error_span!("span b");
+    span_b.set_from_bytes(span.get_context_bytes());
+    assert_eq!(span_b.get_context().get_baggage("tenant"), Some("tenant_a"));
+    let child = error_span!(parent: &span_b, "child of b");
+    assert_eq!(child.get_context().get_baggage("tenant"), Some("tenant_a"));
+
+    let mut headers = std::collections::HashMap::new();
+    span.get_context_headers(&mut headers);
+    assert!(headers.contains_key("traceparent"));
+    assert_eq!(
+        headers.get("baggage").map(String::as_str),
+        Some("tenant=tenant_a")
+    );
+    let span_c = error_span!("span c");
+    span_c.set_from_headers(&headers);
+    let context = span_c.get_context().with_baggage("request", "1");
+    assert_eq!(context.get_baggage("tenant"), Some("tenant_a"));
+    assert_eq!(context.baggage().count(), 2);
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn propagator_test() {
+    use observability::Propagator;
+    use std::collections::HashMap;
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let trace_id = |span: &Span| {
+        let mut headers = HashMap::new();
+        span.get_context_headers(&mut headers);
+        headers["traceparent"]
+            .split('-')
+            .nth(1)
+            .unwrap()
+            .to_string()
+    };
+    let span = error_span!("sender");
+    let expected = trace_id(&span);
+    for propagator in [
+        Propagator::TraceContext,
+        Propagator::B3Single,
+        Propagator::B3Multi,
+        Propagator::Jaeger,
+    ]
+    .iter()
+    {
+        let mut headers = HashMap::new();
+        span.get_context_headers_with(*propagator, &mut headers);
+        let receiver = error_span!("receiver");
+        receiver.set_from_headers_with(Propagator::Composite, &headers);
+        assert_eq!(trace_id(&receiver), expected, "{:?}", propagator);
+    }
+    let mut headers = HashMap::new();
+    span.get_context_headers_with(Propagator::Composite, &mut headers);
+    assert!(headers.contains_key("traceparent"));
+    assert!(headers.contains_key("b3"));
+    assert!(headers.contains_key("x-b3-traceid"));
+    assert!(headers.contains_key("uber-trace-id"));
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn wire_error_test() {
+    use observability::errors::WireError;
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let span = error_span!("sender");
+    let receiver = error_span!("receiver");
+    assert_eq!(
+        receiver.try_set_from_bytes(span.get_context_bytes()),
+        Ok(())
+    );
+    assert!(matches!(
+        receiver.try_set_from_bytes(vec![1, 2, 3]),
+        Err(WireError::Deserialize(_))
+    ));
+    // A message pack map of `{"v": 2}`
+    assert_eq!(
+        receiver.try_set_from_bytes(vec![0x81, 0xa1, b'v', 2]),
+        Err(WireError::UnsupportedVersion(2))
+    );
+    // Should log the error instead of panicking.
+    receiver.set_from_bytes(vec![0xff; 8]);
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn bounded_links_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let mut bytes = error_span!("hop")
+        .get_context_with(&[("hop", "0")])
+        .to_bytes();
+    let mut saturated_len = 0;
+    for i in 1..50 {
+        let span = error_span!("hop");
+        span.set_from_bytes(bytes);
+        bytes = span.get_context_with(&[("hop", &i.to_string())]).to_bytes();
+        if i == 30 {
+            saturated_len = bytes.len();
+        }
+    }
+    // Once the chain is truncated it stops growing.
+    // Timestamps can vary the size by a few bytes.
+    assert!(bytes.len() <= saturated_len + 16, "{}", bytes.len());
+    let span = error_span!("hop");
+    span.set_from_bytes(bytes);
+    let context = span.get_context().to_string();
+    // Default keeps the first 4 and last 12 links.
+    // The last link is this span so it isn't shown.
+    let hops = |r: std::ops::Range<usize>| {
+        r.map(|i| format!(" -> span: hop; hop: {};", i))
+            .collect::<String>()
+    };
+    let expected = format!("{} -> elided: 35;{}", hops(0..4), hops(39..50));
+    let (_, links) = context.split_at(context.find(" ->").unwrap());
+    assert_eq!(links, expected);
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn fan_in_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let task_a = error_span!("task a");
+    let task_b = error_span!("task b");
+    task_b.set_baggage("from", "b");
+    let merge = error_span!("merge");
+    merge.set_contexts(vec![task_a.get_context(), task_b.get_context()]);
+    let context = merge.get_context();
+    let display = context.to_string();
+    assert!(display.contains("-> span: task a;"), "{}", display);
+    assert!(display.contains("+ span: task b;"), "{}", display);
+    assert_eq!(context.get_baggage("from"), Some("b"));
+
+    // Children keep the fan in link.
+    let child = error_span!(parent: &merge, "child");
+    assert!(child.display_context().contains("+ span: task b;"));
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn link_attributes_test() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let span = error_span!("send");
+    let context = span.get_context_with(&[("peer", "10.0.0.1:80"), ("msg_type", "ping")]);
+    let receiver = error_span!("recv");
+    receiver.set_context(Context::try_from_bytes(context.to_bytes()).unwrap());
+    let display = receiver.display_context();
+    assert!(
+        display.contains("-> span: send; peer: 10.0.0.1:80; msg_type: ping;"),
+        "{}",
+        display
+    );
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn queue_latency_test() {
+    use observability::metrics::Boundary;
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    observability::metrics::init();
+    let span = error_span!("send");
+    let bytes = span.get_context_bytes();
+    let msg = observability::MsgWrap::new(1u8, span.get_context()).with_size(100);
+    std::thread::sleep(std::time::Duration::from_millis(5));
+
+    let receiver = error_span!("recv");
+    let _g = receiver.enter();
+    receiver.set_from_bytes(bytes.clone());
+    msg.inner();
+    assert!(Boundary::get(Boundary::Crossings) >= 2);
+    assert!(Boundary::get(Boundary::QueueLatencyMicros) >= 10_000);
+    assert!(Boundary::get(Boundary::MessageBytes) >= 100 + bytes.len() as u64);
+}
+
+#[tokio::test(threaded_scheduler)]
+#[cfg(feature = "opentelemetry-on")]
+async fn in_context_test() {
+    use observability::FutureExt;
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let context = error_span!("rpc client").get_context();
+    let display = async {
+        tokio::time::delay_for(std::time::Duration::from_millis(1)).await;
+        Span::current().display_context()
+    }
+    .in_context(context, error_span!("rpc handler"))
+    .await;
+    assert!(display.contains("-> span: rpc client;"), "{}", display);
+}
+
+#[test]
+#[cfg(feature = "opentelemetry-on")]
+fn msg_wrap_serde_test() {
+    use observability::MsgWrap;
+    #[derive(serde::Serialize, serde::Deserialize)]
+    enum Msg {
+        Ping(MsgWrap<u32>),
+    }
+    observability::init_fmt(observability::Output::OpenTel).ok();
+    let json = error_span!("sender")
+        .in_scope(|| serde_json::to_string(&Msg::Ping(1.into())))
+        .unwrap();
+    let Msg::Ping(msg) = serde_json::from_str(&json).unwrap();
+    let span = error_span!("receiver");
+    let _g = span.enter();
+    assert_eq!(msg.inner(), 1);
+    let display = span.display_context();
+    assert!(display.contains("-> span: sender;"), "{}", display);
+
+    // A bad context doesn't stop the message.
+    let json = r#"{"Ping":{"t":2,"c":[1,2,3]}}"#;
+    let Msg::Ping(msg) = serde_json::from_str(json).unwrap();
+    assert_eq!(msg.without_context(), 2);
+}
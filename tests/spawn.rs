@@ -0,0 +1,59 @@
+#![cfg(feature = "opentelemetry-on")]
+use observability::OpenSpanExt;
+use tracing::*;
+
+fn init() {
+    observability::init_fmt(observability::Output::OpenTel).ok();
+}
+
+#[test]
+fn thread_spawn_test() {
+    init();
+    let span = error_span!("parent");
+    let (thread, display, sent) = span
+        .in_scope(|| {
+            observability::thread::spawn("worker", || {
+                let span = Span::current();
+                let thread = std::thread::current().name().map(String::from);
+                (thread, span.display_context(), span.get_context())
+            })
+        })
+        .join()
+        .unwrap();
+    assert_eq!(thread.as_deref(), Some("worker"));
+    assert!(
+        display.contains("-> span: parent; spawn: worker;"),
+        "{}",
+        display
+    );
+    // The child span is named after the thread.
+    let receiver = error_span!("receiver");
+    receiver.set_context(sent);
+    let display = receiver.display_context();
+    assert!(display.contains("-> span: worker;"), "{}", display);
+}
+
+#[cfg(feature = "tasks")]
+#[tokio::test(threaded_scheduler)]
+async fn task_spawn_test() {
+    init();
+    let span = error_span!("parent");
+    let (task, blocking) = span.in_scope(|| {
+        (
+            observability::task::spawn("task", async { Span::current().display_context() }),
+            observability::task::spawn_blocking("blocking", || Span::current().display_context()),
+        )
+    });
+    let display = task.await.unwrap();
+    assert!(
+        display.contains("-> span: parent; spawn: task;"),
+        "{}",
+        display
+    );
+    let display = blocking.await.unwrap();
+    assert!(
+        display.contains("-> span: parent; spawn: blocking;"),
+        "{}",
+        display
+    );
+}